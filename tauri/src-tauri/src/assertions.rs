@@ -0,0 +1,273 @@
+use regex::Regex;
+use reqwest::header::HeaderMap;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::OnceLock;
+
+// A condition the response has to meet on top of a healthy status code
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Assertion {
+    BodyContains { value: String },
+    BodyNotContains { value: String },
+    BodyMatches { pattern: Pattern },
+    // `path` is a JSON Pointer ("/data/0/status") or a simple JSONPath ("$.data[0].status")
+    JsonEquals { path: String, value: serde_json::Value },
+    HeaderEquals { name: String, value: String },
+    HeaderMatches { name: String, pattern: Pattern },
+}
+
+// A regex as the user wrote it, compiled the first time it is validated or used
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(transparent)]
+pub struct Pattern {
+    source: String,
+    #[serde(skip)]
+    compiled: OnceLock<Regex>,
+}
+
+impl Pattern {
+    pub fn regex(&self) -> Result<&Regex, String> {
+        if let Some(regex) = self.compiled.get() {
+            return Ok(regex);
+        }
+        let regex = Regex::new(&self.source).map_err(|e| format!("invalid pattern '{}': {}", self.source, e))?;
+        Ok(self.compiled.get_or_init(|| regex))
+    }
+}
+
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AssertionFailure {
+    pub assertion: Assertion,
+    pub message: String,
+}
+
+impl Assertion {
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            Assertion::BodyMatches { pattern } | Assertion::HeaderMatches { pattern, .. } => {
+                pattern.regex()?;
+            }
+            Assertion::JsonEquals { path, .. } => {
+                json_pointer(path)?;
+            }
+            _ => {}
+        }
+        if let Assertion::HeaderEquals { name, .. } | Assertion::HeaderMatches { name, .. } = self {
+            reqwest::header::HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| format!("invalid header name '{}'", name))?;
+        }
+        Ok(())
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Assertion::BodyContains { value } => format!("body contains \"{}\"", value),
+            Assertion::BodyNotContains { value } => format!("body does not contain \"{}\"", value),
+            Assertion::BodyMatches { pattern } => format!("body matches /{}/", pattern),
+            Assertion::JsonEquals { path, value } => format!("{} equals {}", path, value),
+            Assertion::HeaderEquals { name, value } => format!("header {} equals \"{}\"", name, value),
+            Assertion::HeaderMatches { name, pattern } => format!("header {} matches /{}/", name, pattern),
+        }
+    }
+
+    fn check(&self, headers: &HeaderMap, body: &str) -> Result<(), String> {
+        match self {
+            Assertion::BodyContains { value } => {
+                if body.contains(value.as_str()) {
+                    Ok(())
+                } else {
+                    Err("text not found in body".to_string())
+                }
+            }
+            Assertion::BodyNotContains { value } => {
+                if body.contains(value.as_str()) {
+                    Err("text found in body".to_string())
+                } else {
+                    Ok(())
+                }
+            }
+            Assertion::BodyMatches { pattern } => {
+                if pattern.regex()?.is_match(body) {
+                    Ok(())
+                } else {
+                    Err("body does not match".to_string())
+                }
+            }
+            Assertion::JsonEquals { path, value } => {
+                let json: serde_json::Value =
+                    serde_json::from_str(body).map_err(|e| format!("body is not JSON: {}", e))?;
+                match json.pointer(&json_pointer(path)?) {
+                    Some(actual) if actual == value => Ok(()),
+                    Some(actual) => Err(format!("found {}", actual)),
+                    None => Err(format!("{} not present", path)),
+                }
+            }
+            Assertion::HeaderEquals { name, value } => match header_value(headers, name) {
+                Some(actual) if actual == *value => Ok(()),
+                Some(actual) => Err(format!("found \"{}\"", actual)),
+                None => Err(format!("header {} missing", name)),
+            },
+            Assertion::HeaderMatches { name, pattern } => {
+                let re = pattern.regex()?;
+                match header_value(headers, name) {
+                    Some(actual) if re.is_match(&actual) => Ok(()),
+                    Some(actual) => Err(format!("found \"{}\"", actual)),
+                    None => Err(format!("header {} missing", name)),
+                }
+            }
+        }
+    }
+}
+
+// Runs every assertion (not just up to the first failure) so the user sees all that broke
+pub fn run_all(assertions: &[Assertion], headers: &HeaderMap, body: &[u8]) -> Vec<AssertionFailure> {
+    if assertions.is_empty() {
+        return Vec::new();
+    }
+    let body = String::from_utf8_lossy(body);
+    assertions
+        .iter()
+        .filter_map(|assertion| {
+            assertion.check(headers, &body).err().map(|message| AssertionFailure {
+                assertion: assertion.clone(),
+                message,
+            })
+        })
+        .collect()
+}
+
+fn header_value(headers: &HeaderMap, name: &str) -> Option<String> {
+    let values: Vec<&str> = headers
+        .get_all(name)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .collect();
+    if values.is_empty() {
+        None
+    } else {
+        Some(values.join(", "))
+    }
+}
+
+// Turns either path syntax into a JSON Pointer for serde_json::Value::pointer
+fn json_pointer(path: &str) -> Result<String, String> {
+    let path = path.trim();
+    if path.is_empty() || path.starts_with('/') {
+        return Ok(path.to_string());
+    }
+    let Some(rest) = path.strip_prefix('$') else {
+        return Err(format!("'{}' is neither a JSON Pointer nor a JSONPath starting with $", path));
+    };
+
+    let escape = |segment: &str| segment.replace('~', "~0").replace('/', "~1");
+    let mut pointer = String::new();
+    let mut chars = rest.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '.' => {
+                let mut key = String::new();
+                while let Some(&next) = chars.peek() {
+                    if next == '.' || next == '[' {
+                        break;
+                    }
+                    key.push(next);
+                    chars.next();
+                }
+                if key.is_empty() {
+                    return Err(format!("empty key in '{}'", path));
+                }
+                pointer.push('/');
+                pointer.push_str(&escape(&key));
+            }
+            '[' => {
+                let mut inner = String::new();
+                for next in chars.by_ref() {
+                    if next == ']' {
+                        break;
+                    }
+                    inner.push(next);
+                }
+                let inner = inner.trim();
+                let key = if let Some(quoted) = inner
+                    .strip_prefix('\'')
+                    .and_then(|s| s.strip_suffix('\''))
+                    .or_else(|| inner.strip_prefix('"').and_then(|s| s.strip_suffix('"')))
+                {
+                    quoted.to_string()
+                } else if inner.parse::<usize>().is_ok() {
+                    inner.to_string()
+                } else {
+                    return Err(format!("unsupported selector [{}] in '{}'", inner, path));
+                };
+                pointer.push('/');
+                pointer.push_str(&escape(&key));
+            }
+            _ => return Err(format!("unexpected '{}' in '{}'", c, path)),
+        }
+    }
+    Ok(pointer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assertion(value: serde_json::Value) -> Assertion {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn json_pointers_pass_through() {
+        assert_eq!(json_pointer("/data/0/status").unwrap(), "/data/0/status");
+        assert_eq!(json_pointer("").unwrap(), "");
+    }
+
+    #[test]
+    fn json_paths_become_pointers() {
+        assert_eq!(json_pointer("$").unwrap(), "");
+        assert_eq!(json_pointer("$.data[0].status").unwrap(), "/data/0/status");
+        assert_eq!(json_pointer("$['a/b'][\"c~d\"]").unwrap(), "/a~1b/c~0d");
+        assert_eq!(json_pointer(" $.status ").unwrap(), "/status");
+    }
+
+    #[test]
+    fn unsupported_json_paths_are_rejected() {
+        assert!(json_pointer("data.status").is_err());
+        assert!(json_pointer("$..status").is_err());
+        assert!(json_pointer("$.data[*]").is_err());
+        assert!(json_pointer("$status").is_err());
+    }
+
+    #[test]
+    fn json_equals_compares_the_value_at_the_path() {
+        let checks = [assertion(serde_json::json!({ "type": "json_equals", "path": "$.data[1].ok", "value": true }))];
+        let headers = HeaderMap::new();
+        assert!(run_all(&checks, &headers, br#"{"data":[{},{"ok":true}]}"#).is_empty());
+        let failures = run_all(&checks, &headers, br#"{"data":[{},{"ok":false}]}"#);
+        assert_eq!(failures[0].message, "found false");
+        let failures = run_all(&checks, &headers, br#"{"data":[]}"#);
+        assert_eq!(failures[0].message, "$.data[1].ok not present");
+    }
+
+    #[test]
+    fn patterns_are_checked_when_validated() {
+        let good = assertion(serde_json::json!({ "type": "body_matches", "pattern": "^ok \\d+$" }));
+        assert!(good.validate().is_ok());
+        assert!(run_all(&[good], &HeaderMap::new(), b"ok 200").is_empty());
+        let bad = assertion(serde_json::json!({ "type": "header_matches", "name": "server", "pattern": "(" }));
+        assert!(bad.validate().unwrap_err().starts_with("invalid pattern '('"));
+    }
+}
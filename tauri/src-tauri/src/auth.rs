@@ -4,6 +4,7 @@ use rand::Rng;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use std::sync::mpsc;
+use tauri::Manager;
 use tauri_plugin_keyring::KeyringExt;
 use tiny_http::{Response, Server};
 use url::Url;
@@ -23,6 +24,32 @@ pub struct TokenResponse {
     pub id_token: Option<String>,
 }
 
+// The Azure AD app registration sign-in goes through. The frontend reads the same CLIENT_ID and
+// TENANT_ID from .env via get_env; the backend loads them once at startup and keeps the pair the
+// user last signed in with, for work (like Graph email) that runs without the window.
+#[derive(Debug, Clone)]
+pub struct AppRegistration {
+    pub client_id: String,
+    pub tenant_id: String,
+}
+
+impl AppRegistration {
+    pub fn new(client_id: &str, tenant_id: &str) -> Option<AppRegistration> {
+        let (client_id, tenant_id) = (client_id.trim(), tenant_id.trim());
+        (!client_id.is_empty() && !tenant_id.is_empty()).then(|| AppRegistration {
+            client_id: client_id.to_string(),
+            tenant_id: tenant_id.to_string(),
+        })
+    }
+
+    pub fn from_env() -> Option<AppRegistration> {
+        AppRegistration::new(
+            &std::env::var("CLIENT_ID").unwrap_or_default(),
+            &std::env::var("TENANT_ID").unwrap_or_default(),
+        )
+    }
+}
+
 pub fn generate_pkce() -> (String, String) {
     let code_verifier: String = rand::thread_rng()
         .sample_iter(&rand::distributions::Alphanumeric)
@@ -121,7 +148,7 @@ pub async fn exchange_code_for_token(
     }
 
     // Try to fetch user photo (best-effort). This does not change success of login.
-    let _ = fetch_user_photo(&token_response.access_token).await;
+    let _ = fetch_user_photo(&graph_base_url(app_handle), &token_response.access_token).await;
 
     // if let Ok(Some(saved)) = app_handle
     //     .keyring()
@@ -249,17 +276,52 @@ pub fn extract_user_from_id_token_or_os(
             }
         }
     }
-    Ok(std::env::var("USERNAME")
+    Ok(os_user())
+}
+
+fn os_user() -> String {
+    std::env::var("USERNAME")
         .or_else(|_| std::env::var("USER"))
-        .unwrap_or_else(|_| "unknown".into()))
+        .unwrap_or_else(|_| "unknown".into())
+}
+
+// The account whose token is stored in the keyring, if anyone is signed in
+pub fn signed_in_user(app_handle: &tauri::AppHandle) -> Option<String> {
+    let service = app_handle.package_info().name.to_string();
+    let last_user_key = format!("{}::last_user", &service);
+    let last_user = app_handle.keyring().get_password(&service, &last_user_key).ok().flatten()?;
+    load_token_from_keyring(app_handle, &last_user).ok().flatten().map(|_| last_user)
+}
+
+// The signed-in user (from the stored id token) or, if nobody is signed in, the OS user
+pub fn current_user(app_handle: &tauri::AppHandle) -> String {
+    let service = app_handle.package_info().name.to_string();
+    let last_user_key = format!("{}::last_user", &service);
+    if let Ok(Some(last_user)) = app_handle.keyring().get_password(&service, &last_user_key) {
+        if let Ok(Some(stored)) = load_token_from_keyring(app_handle, &last_user) {
+            if let Ok(user) = extract_user_from_id_token_or_os(&stored.token) {
+                return user;
+            }
+        }
+        return last_user;
+    }
+    os_user()
+}
+
+// Microsoft Graph endpoint from settings, without a trailing slash
+pub fn graph_base_url(app_handle: &tauri::AppHandle) -> String {
+    let state = app_handle.state::<crate::AppState>();
+    let base = state.settings.lock().unwrap().graph_base_url.clone();
+    base.trim_end_matches('/').to_string()
 }
 
 pub async fn fetch_user_photo(
+    graph_base_url: &str,
     access_token: &str,
 ) -> Result<Option<String>, Box<dyn std::error::Error + Send + Sync>> {
     let client = reqwest::Client::new();
     let res = client
-        .get("https://graph.microsoft.com/v1.0/me/photo/$value")
+        .get(format!("{}/me/photo/$value", graph_base_url))
         .bearer_auth(access_token)
         .send()
         .await
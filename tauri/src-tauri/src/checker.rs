@@ -0,0 +1,319 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error as _;
+use std::sync::{Arc, Mutex};
+use tokio::sync::Semaphore;
+use tokio::time::Instant;
+use url::Url;
+
+use crate::assertions::{self, AssertionFailure};
+use crate::monitors::Monitor;
+use crate::settings::AppSettings;
+use crate::tls::{self, CertificateInfo};
+
+const REQUEST_TIMEOUT_SECS: u64 = 10;
+// Bodies are read up to this size; the rest is never downloaded and assertions see only this much
+const MAX_BODY_BYTES: usize = 5 * 1024 * 1024;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CheckResult {
+    pub checked_at: String,
+    pub status_code: Option<u16>,
+    // time from sending the request until the whole body was read
+    pub response_time_ms: u64,
+    pub response_size: Option<u64>,
+    // the body was cut off at MAX_BODY_BYTES, which is what response_size then holds
+    #[serde(default)]
+    pub body_truncated: bool,
+    pub error: Option<CheckError>,
+    // only for https monitors
+    #[serde(default)]
+    pub certificate: Option<CertificateInfo>,
+    #[serde(default)]
+    pub certificate_error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CheckError {
+    pub class: ErrorClass,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub failed_assertions: Vec<AssertionFailure>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorClass {
+    Dns,
+    ConnectionRefused,
+    Tls,
+    Timeout,
+    RedirectLoop,
+    BodyRead,
+    // connection failed for some other reason (reset, unreachable, ...)
+    Connect,
+    // the server answered, but with a non-success status
+    HttpStatus,
+    // the response came back fine but one or more content assertions failed
+    Assertion,
+    Other,
+}
+
+impl ErrorClass {
+    // the snake_case name used in JSON and the history database
+    pub fn code(&self) -> String {
+        serde_json::to_value(self)
+            .ok()
+            .and_then(|v| v.as_str().map(|s| s.to_string()))
+            .unwrap_or_default()
+    }
+
+    pub fn describe(&self) -> &'static str {
+        match self {
+            ErrorClass::Dns => "DNS lookup failed",
+            ErrorClass::ConnectionRefused => "connection refused",
+            ErrorClass::Tls => "TLS error",
+            ErrorClass::Timeout => "timed out",
+            ErrorClass::RedirectLoop => "too many redirects",
+            ErrorClass::BodyRead => "failed to read response body",
+            ErrorClass::Connect => "connection failed",
+            ErrorClass::HttpStatus => "unexpected HTTP status",
+            ErrorClass::Assertion => "content assertion failed",
+            ErrorClass::Other => "request failed",
+        }
+    }
+}
+
+// Runs website checks over shared, pooled HTTP clients. A global semaphore caps the number
+// of checks in flight and a semaphore per host keeps us from hammering any single server.
+pub struct CheckEngine {
+    clients: Clients,
+    global: Arc<Semaphore>,
+    per_host: Mutex<HashMap<String, Arc<Semaphore>>>,
+    per_host_limit: usize,
+    certificates: Arc<tls::ProbeCache>,
+}
+
+impl CheckEngine {
+    pub fn new(settings: &AppSettings) -> Result<CheckEngine, String> {
+        Ok(CheckEngine::with_clients(Clients::new()?, Arc::default(), settings))
+    }
+
+    // Same connection pools and certificates, new limits. Checks already running keep their old permits.
+    pub fn with_limits(&self, settings: &AppSettings) -> CheckEngine {
+        CheckEngine::with_clients(self.clients.clone(), self.certificates.clone(), settings)
+    }
+
+    fn with_clients(clients: Clients, certificates: Arc<tls::ProbeCache>, settings: &AppSettings) -> CheckEngine {
+        CheckEngine {
+            clients,
+            global: Arc::new(Semaphore::new(settings.max_concurrent_checks)),
+            per_host: Mutex::new(HashMap::new()),
+            per_host_limit: settings.max_checks_per_host,
+            certificates,
+        }
+    }
+
+    fn build_request(&self, monitor: &Monitor) -> reqwest::RequestBuilder {
+        let spec = &monitor.request;
+        let client = if spec.follow_redirects {
+            &self.clients.follow
+        } else {
+            &self.clients.no_follow
+        };
+        // validated when the monitor was saved
+        let method = reqwest::Method::from_bytes(spec.method.as_bytes()).unwrap_or(reqwest::Method::GET);
+        let mut request = client.request(method, &monitor.url);
+        for (name, value) in &spec.headers {
+            request = request.header(name, value);
+        }
+        if let Some(body) = &spec.body {
+            let has_content_type = spec.headers.keys().any(|k| k.eq_ignore_ascii_case("content-type"));
+            if !has_content_type && serde_json::from_str::<serde_json::Value>(body).is_ok() {
+                request = request.header(reqwest::header::CONTENT_TYPE, "application/json");
+            }
+            request = request.body(body.clone());
+        }
+        if let Some(timeout) = spec.timeout_secs {
+            request = request.timeout(std::time::Duration::from_secs(timeout));
+        }
+        request
+    }
+
+    fn host_semaphore(&self, url: &str) -> Arc<Semaphore> {
+        let host = Url::parse(url)
+            .ok()
+            .and_then(|u| u.host_str().map(|h| h.to_string()))
+            .unwrap_or_default();
+        let mut per_host = self.per_host.lock().unwrap();
+        per_host
+            .entry(host)
+            .or_insert_with(|| Arc::new(Semaphore::new(self.per_host_limit)))
+            .clone()
+    }
+
+    pub async fn check(&self, monitor: &Monitor) -> CheckResult {
+        let url = monitor.url.as_str();
+        // take the host slot first so a queue for one busy host doesn't hold global slots
+        let host = self.host_semaphore(url);
+        let host_permit = host.acquire_owned().await.expect("host semaphore closed");
+        let permit = self.global.acquire().await.expect("check semaphore closed");
+
+        let started = Instant::now();
+        let mut result = CheckResult {
+            checked_at: chrono::Utc::now().to_rfc3339(),
+            status_code: None,
+            response_time_ms: 0,
+            response_size: None,
+            body_truncated: false,
+            error: None,
+            certificate: None,
+            certificate_error: None,
+        };
+
+        match self.build_request(monitor).send().await {
+            Ok(response) => {
+                let status = response.status();
+                let headers = response.headers().clone();
+                result.status_code = Some(status.as_u16());
+                match read_body(response).await {
+                    Ok((body, truncated)) => {
+                        result.response_size = Some(body.len() as u64);
+                        result.body_truncated = truncated;
+                        if !monitor.request.accepts(status.as_u16()) {
+                            result.error = Some(CheckError {
+                                class: ErrorClass::HttpStatus,
+                                message: format!("HTTP {}", status),
+                                failed_assertions: Vec::new(),
+                            });
+                        } else {
+                            let failed = assertions::run_all(&monitor.assertions, &headers, &body);
+                            if !failed.is_empty() {
+                                let message = failed
+                                    .iter()
+                                    .map(|f| format!("{} ({})", f.assertion.describe(), f.message))
+                                    .collect::<Vec<_>>()
+                                    .join("; ");
+                                result.error = Some(CheckError {
+                                    class: ErrorClass::Assertion,
+                                    message,
+                                    failed_assertions: failed,
+                                });
+                            }
+                        }
+                    }
+                    Err(e) => result.error = Some(classify_error(&e)),
+                }
+            }
+            Err(e) => result.error = Some(classify_error(&e)),
+        }
+        result.response_time_ms = started.elapsed().as_millis() as u64;
+        // the certificate probe is a connection of its own and mostly cached, so it doesn't
+        // keep other checks waiting
+        drop(permit);
+        drop(host_permit);
+
+        if let Some((host, port)) = tls_target(url) {
+            match self.certificates.probe(&host, port).await {
+                Ok(certificate) => result.certificate = Some(certificate),
+                Err(e) => result.certificate_error = Some(e),
+            }
+        }
+        result
+    }
+}
+
+// Reads the body a chunk at a time, stopping at MAX_BODY_BYTES. Returns whether it was cut off.
+async fn read_body(mut response: reqwest::Response) -> Result<(Vec<u8>, bool), reqwest::Error> {
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        let room = MAX_BODY_BYTES - body.len();
+        if chunk.len() > room {
+            body.extend_from_slice(&chunk[..room]);
+            return Ok((body, true));
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok((body, false))
+}
+
+fn tls_target(url: &str) -> Option<(String, u16)> {
+    let url = Url::parse(url).ok()?;
+    if url.scheme() != "https" {
+        return None;
+    }
+    let host = url.host_str()?.trim_start_matches('[').trim_end_matches(']').to_string();
+    Some((host, url.port_or_known_default()?))
+}
+
+// One pool per redirect policy; reqwest fixes the policy when the client is built
+#[derive(Clone)]
+struct Clients {
+    follow: reqwest::Client,
+    no_follow: reqwest::Client,
+}
+
+impl Clients {
+    fn new() -> Result<Clients, String> {
+        let builder = || {
+            reqwest::Client::builder().timeout(std::time::Duration::from_secs(REQUEST_TIMEOUT_SECS))
+        };
+        Ok(Clients {
+            follow: builder().build().map_err(|e| e.to_string())?,
+            no_follow: builder()
+                .redirect(reqwest::redirect::Policy::none())
+                .build()
+                .map_err(|e| e.to_string())?,
+        })
+    }
+}
+
+// reqwest flattens most failures into a few flags, so look through the whole source
+// chain (hyper, the resolver, the TLS stack, io errors) to find out what went wrong
+fn classify_error(error: &reqwest::Error) -> CheckError {
+    let mut messages = vec![error.to_string()];
+    let mut io_kind = None;
+    let mut source = error.source();
+    while let Some(inner) = source {
+        messages.push(inner.to_string());
+        if let Some(io) = inner.downcast_ref::<std::io::Error>() {
+            io_kind.get_or_insert(io.kind());
+        }
+        source = inner.source();
+    }
+    messages.dedup();
+    let message = messages.join(": ");
+    let text = message.to_lowercase();
+
+    let class = if error.is_timeout() || io_kind == Some(std::io::ErrorKind::TimedOut) {
+        ErrorClass::Timeout
+    } else if error.is_redirect() {
+        ErrorClass::RedirectLoop
+    } else if error.is_body() || error.is_decode() {
+        ErrorClass::BodyRead
+    } else if io_kind == Some(std::io::ErrorKind::ConnectionRefused) || text.contains("connection refused") {
+        ErrorClass::ConnectionRefused
+    } else if text.contains("dns error")
+        || text.contains("failed to lookup address")
+        || text.contains("name or service not known")
+        || text.contains("no such host")
+    {
+        ErrorClass::Dns
+    } else if text.contains("certificate")
+        || text.contains("tls")
+        || text.contains("ssl")
+        || text.contains("handshake")
+    {
+        ErrorClass::Tls
+    } else if error.is_connect() {
+        ErrorClass::Connect
+    } else {
+        ErrorClass::Other
+    };
+
+    CheckError {
+        class,
+        message,
+        failed_assertions: Vec::new(),
+    }
+}
@@ -0,0 +1,126 @@
+use chrono::{DateTime, Utc};
+use std::sync::Mutex;
+use tokio::sync::watch;
+use tokio::time::{Duration, Instant};
+
+// Anything smaller is scheduling noise or NTP slewing
+const JUMP_THRESHOLD: Duration = Duration::from_secs(30);
+
+// Something made wall-clock time and our timers disagree
+#[derive(Debug, Clone, Copy)]
+pub enum Jump {
+    // time passed that we didn't get to run in: the machine slept, or the clock was set forward
+    Gap { since: DateTime<Utc> },
+    // the clock was set back
+    Backward { secs: i64 },
+}
+
+// Spots suspends and clock changes by comparing wall-clock time with the monotonic clock
+// between observations. Depending on the platform the monotonic clock either stops during
+// sleep (wall time gets ahead of it) or keeps going (a timer fires far later than planned);
+// both count as a gap.
+pub struct Clock {
+    last: Mutex<Option<(Instant, DateTime<Utc>)>>,
+    // bumped on every jump; work started before a jump can tell it is stale
+    jumps: watch::Sender<u64>,
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Clock {
+            last: Mutex::new(None),
+            jumps: watch::channel(0).0,
+        }
+    }
+}
+
+impl Clock {
+    // `planned` is how long the caller meant to be away since its previous observation
+    pub fn observe(&self, planned: Duration) -> Option<Jump> {
+        let now = (Instant::now(), Utc::now());
+        let (then_instant, then_wall) = self.last.lock().unwrap().replace(now)?;
+        let monotonic = now.0 - then_instant;
+        let wall_ms = (now.1 - then_wall).num_milliseconds();
+        let drift_ms = wall_ms - monotonic.as_millis() as i64;
+        let threshold_ms = JUMP_THRESHOLD.as_millis() as i64;
+
+        let jump = if monotonic > planned + JUMP_THRESHOLD || drift_ms > threshold_ms {
+            Jump::Gap { since: then_wall }
+        } else if drift_ms < -threshold_ms {
+            Jump::Backward { secs: -drift_ms / 1000 }
+        } else {
+            return None;
+        };
+        self.jumps.send_modify(|generation| *generation += 1);
+        Some(jump)
+    }
+
+    pub fn generation(&self) -> u64 {
+        *self.jumps.borrow()
+    }
+
+    // Changes whenever a jump is detected
+    pub fn subscribe(&self) -> watch::Receiver<u64> {
+        self.jumps.subscribe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A clock whose previous observation was `monotonic_secs` ago by the timers and
+    // `wall_secs` ago by the wall clock
+    fn observed_ago(monotonic_secs: u64, wall_secs: i64) -> Clock {
+        let clock = Clock::default();
+        let instant = Instant::now().checked_sub(Duration::from_secs(monotonic_secs)).unwrap();
+        *clock.last.lock().unwrap() = Some((instant, Utc::now() - chrono::Duration::seconds(wall_secs)));
+        clock
+    }
+
+    // the few milliseconds the test itself takes can tip the whole-second count either way
+    fn assert_about(secs: i64, expected: i64) {
+        assert!((expected - 1..=expected).contains(&secs), "{} is not about {}", secs, expected);
+    }
+
+    #[test]
+    fn the_first_observation_is_never_a_jump() {
+        let clock = Clock::default();
+        assert!(clock.observe(Duration::ZERO).is_none());
+        assert_eq!(clock.generation(), 0);
+    }
+
+    #[test]
+    fn small_differences_are_noise() {
+        assert!(observed_ago(60, 80).observe(Duration::from_secs(60)).is_none());
+        assert!(observed_ago(60, 40).observe(Duration::from_secs(60)).is_none());
+        // a timer up to the threshold late
+        assert!(observed_ago(85, 85).observe(Duration::from_secs(60)).is_none());
+    }
+
+    #[test]
+    fn wall_time_running_ahead_is_a_gap() {
+        let clock = observed_ago(60, 180);
+        match clock.observe(Duration::from_secs(60)) {
+            Some(Jump::Gap { since }) => assert_about((Utc::now() - since).num_seconds(), 180),
+            other => panic!("expected a gap, got {:?}", other),
+        }
+        assert_eq!(clock.generation(), 1);
+    }
+
+    #[test]
+    fn a_timer_firing_late_is_a_gap() {
+        match observed_ago(300, 300).observe(Duration::from_secs(60)) {
+            Some(Jump::Gap { since }) => assert_about((Utc::now() - since).num_seconds(), 300),
+            other => panic!("expected a gap, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn wall_time_falling_behind_is_a_backward_jump() {
+        match observed_ago(60, -60).observe(Duration::from_secs(60)) {
+            Some(Jump::Backward { secs }) => assert_about(secs, 120),
+            other => panic!("expected a backward jump, got {:?}", other),
+        }
+    }
+}
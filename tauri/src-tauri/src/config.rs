@@ -0,0 +1,51 @@
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::path::PathBuf;
+use tauri::{AppHandle, Manager};
+
+// On-disk envelope for config files: `{ "version": N, ...data }`
+#[derive(Serialize, Deserialize)]
+struct Versioned<T> {
+    version: u32,
+    #[serde(flatten)]
+    data: T,
+}
+
+pub fn config_path(app_handle: &AppHandle, file_name: &str) -> Result<PathBuf, String> {
+    let dir = app_handle.path().app_config_dir().map_err(|e| e.to_string())?;
+    Ok(dir.join(file_name))
+}
+
+pub fn load_versioned<T: DeserializeOwned>(
+    path: &PathBuf,
+    current_version: u32,
+) -> Result<Option<T>, String> {
+    let json = match std::fs::read_to_string(path) {
+        Ok(json) => json,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("failed to read {}: {}", path.display(), e)),
+    };
+    let file: Versioned<T> = serde_json::from_str(&json)
+        .map_err(|e| format!("failed to parse {}: {}", path.display(), e))?;
+    if file.version > current_version {
+        return Err(format!(
+            "{} was written by a newer version (file version {}, supported {})",
+            path.display(),
+            file.version,
+            current_version
+        ));
+    }
+    Ok(Some(file.data))
+}
+
+pub fn save_versioned<T: Serialize>(path: &PathBuf, version: u32, data: &T) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    let json = serde_json::to_string_pretty(&Versioned { version, data })
+        .map_err(|e| e.to_string())?;
+    // write to a sibling file and rename so a crash never leaves a half-written config
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, json).map_err(|e| e.to_string())?;
+    std::fs::rename(&tmp, path).map_err(|e| e.to_string())?;
+    Ok(())
+}
@@ -0,0 +1,164 @@
+use rusqlite::Connection;
+use std::path::Path;
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager};
+
+const DATABASE_FILE: &str = "history.db";
+
+// Schema changes, applied in order. PRAGMA user_version records how many have run,
+// so only ever append to this list.
+const MIGRATIONS: &[&str] = &[
+    // 1: raw check results
+    "CREATE TABLE checks (
+        id INTEGER PRIMARY KEY,
+        monitor_id TEXT NOT NULL,
+        checked_at INTEGER NOT NULL,
+        state TEXT NOT NULL,
+        observed_state TEXT NOT NULL,
+        status_code INTEGER,
+        latency_ms INTEGER NOT NULL,
+        response_size INTEGER,
+        error_class TEXT,
+        error_message TEXT
+    );
+    CREATE INDEX checks_monitor_time ON checks (monitor_id, checked_at);",
+    // 2: hourly uptime rollups, kept up to date as checks come in
+    "CREATE TABLE uptime_hourly (
+        monitor_id TEXT NOT NULL,
+        hour_start INTEGER NOT NULL,
+        monitored_ms INTEGER NOT NULL DEFAULT 0,
+        downtime_ms INTEGER NOT NULL DEFAULT 0,
+        latency_sum INTEGER NOT NULL DEFAULT 0,
+        latency_count INTEGER NOT NULL DEFAULT 0,
+        latency_histogram TEXT,
+        PRIMARY KEY (monitor_id, hour_start)
+    );
+    CREATE INDEX uptime_hourly_time ON uptime_hourly (hour_start);
+    CREATE TABLE uptime_cursor (
+        monitor_id TEXT PRIMARY KEY,
+        last_checked_at INTEGER NOT NULL,
+        last_state TEXT NOT NULL
+    );",
+    // 3: incidents, the monitors caught up in them and operator notes
+    "CREATE TABLE incidents (
+        id INTEGER PRIMARY KEY,
+        status TEXT NOT NULL,
+        group_name TEXT,
+        started_at INTEGER NOT NULL,
+        ended_at INTEGER,
+        first_error TEXT,
+        last_error TEXT,
+        acknowledged_by TEXT,
+        acknowledged_at INTEGER
+    );
+    CREATE INDEX incidents_started ON incidents (started_at);
+    CREATE INDEX incidents_status ON incidents (status);
+    CREATE TABLE incident_monitors (
+        incident_id INTEGER NOT NULL REFERENCES incidents (id) ON DELETE CASCADE,
+        monitor_id TEXT NOT NULL,
+        joined_at INTEGER NOT NULL,
+        recovered_at INTEGER,
+        PRIMARY KEY (incident_id, monitor_id)
+    );
+    CREATE INDEX incident_monitors_monitor ON incident_monitors (monitor_id);
+    CREATE TABLE incident_notes (
+        id INTEGER PRIMARY KEY,
+        incident_id INTEGER NOT NULL REFERENCES incidents (id) ON DELETE CASCADE,
+        author TEXT NOT NULL,
+        created_at INTEGER NOT NULL,
+        body TEXT NOT NULL
+    );
+    CREATE INDEX incident_notes_incident ON incident_notes (incident_id);",
+    // 4: acknowledged / snoozed notifications per monitor
+    "CREATE TABLE monitor_state (
+        monitor_id TEXT PRIMARY KEY,
+        acknowledged INTEGER NOT NULL DEFAULT 0,
+        snoozed_until INTEGER
+    );",
+    // 5: alert deliveries to notification channels
+    "CREATE TABLE deliveries (
+        id INTEGER PRIMARY KEY,
+        channel_id TEXT NOT NULL,
+        event TEXT NOT NULL,
+        monitor_id TEXT,
+        incident_id INTEGER,
+        attempts INTEGER NOT NULL,
+        delivered INTEGER NOT NULL,
+        status_code INTEGER,
+        error TEXT,
+        created_at INTEGER NOT NULL,
+        completed_at INTEGER NOT NULL
+    );
+    CREATE INDEX deliveries_channel_time ON deliveries (channel_id, created_at);",
+    // 6: escalation steps still waiting to fire; event is the alert as JSON
+    "CREATE TABLE escalations (
+        id INTEGER PRIMARY KEY,
+        incident_id INTEGER NOT NULL,
+        due_at INTEGER NOT NULL,
+        after_mins INTEGER NOT NULL,
+        channel_ids TEXT NOT NULL,
+        event TEXT NOT NULL
+    );
+    CREATE INDEX escalations_due ON escalations (due_at);",
+    // 7: paused monitoring; monitor_id is NULL when everything was paused
+    "CREATE TABLE pauses (
+        id INTEGER PRIMARY KEY,
+        monitor_id TEXT,
+        started_at INTEGER NOT NULL,
+        ended_at INTEGER,
+        paused_until INTEGER
+    );
+    CREATE INDEX pauses_started ON pauses (started_at);",
+];
+
+pub struct Database {
+    conn: Mutex<Connection>,
+}
+
+impl Database {
+    pub fn open_for_app(app_handle: &AppHandle) -> Result<Database, String> {
+        let dir = app_handle.path().app_data_dir().map_err(|e| e.to_string())?;
+        std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+        Database::open(&dir.join(DATABASE_FILE))
+    }
+
+    pub fn open(path: &Path) -> Result<Database, String> {
+        let conn = Connection::open(path).map_err(|e| format!("failed to open {}: {}", path.display(), e))?;
+        // WAL lets history queries run while checks are being written
+        conn.execute_batch("PRAGMA journal_mode = WAL; PRAGMA foreign_keys = ON;")
+            .map_err(|e| e.to_string())?;
+        let db = Database {
+            conn: Mutex::new(conn),
+        };
+        db.migrate()?;
+        Ok(db)
+    }
+
+    fn migrate(&self) -> Result<(), String> {
+        let mut conn = self.conn.lock().unwrap();
+        let version = conn
+            .query_row("PRAGMA user_version", [], |row| row.get::<_, i64>(0))
+            .map_err(|e| e.to_string())? as usize;
+        if version > MIGRATIONS.len() {
+            return Err(format!(
+                "database schema version {} is newer than this app supports ({})",
+                version,
+                MIGRATIONS.len()
+            ));
+        }
+        for (index, sql) in MIGRATIONS.iter().enumerate().skip(version) {
+            let tx = conn.transaction().map_err(|e| e.to_string())?;
+            tx.execute_batch(sql)
+                .map_err(|e| format!("database migration {} failed: {}", index + 1, e))?;
+            tx.pragma_update(None, "user_version", (index + 1) as i64)
+                .map_err(|e| e.to_string())?;
+            tx.commit().map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
+    pub fn with_conn<T>(&self, f: impl FnOnce(&mut Connection) -> rusqlite::Result<T>) -> Result<T, String> {
+        let mut conn = self.conn.lock().unwrap();
+        f(&mut conn).map_err(|e| e.to_string())
+    }
+}
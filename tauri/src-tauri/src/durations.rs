@@ -0,0 +1,60 @@
+use chrono::Duration;
+
+// "90s", "30m", "1h", "2d" or a bare number of seconds, up to `max_days`. `what` names the
+// thing being timed in the error, e.g. "a snooze" or "a pause".
+pub fn parse(value: &str, max_days: i64, what: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let split = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: i64 = number
+        .parse()
+        .map_err(|_| format!("invalid duration '{}' (use e.g. 30m, 1h or 2d)", value))?;
+    let duration = match unit.trim() {
+        "" | "s" => Duration::try_seconds(number),
+        "m" => Duration::try_minutes(number),
+        "h" => Duration::try_hours(number),
+        "d" => Duration::try_days(number),
+        other => return Err(format!("unknown duration unit '{}' (use s, m, h or d)", other)),
+    };
+    // None is a number too big for chrono, which is over the limit too
+    match duration {
+        Some(duration) if duration <= Duration::days(max_days) => Ok(duration),
+        _ => Err(format!("{} can last at most {} days", what, max_days)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations_take_a_unit_or_seconds() {
+        assert_eq!(parse("90", 7, "a snooze"), Ok(Duration::seconds(90)));
+        assert_eq!(parse("90s", 7, "a snooze"), Ok(Duration::seconds(90)));
+        assert_eq!(parse(" 30m ", 7, "a snooze"), Ok(Duration::minutes(30)));
+        assert_eq!(parse("1h", 7, "a snooze"), Ok(Duration::hours(1)));
+        assert_eq!(parse("2 d", 7, "a snooze"), Ok(Duration::days(2)));
+    }
+
+    #[test]
+    fn durations_are_limited_to_max_days() {
+        assert_eq!(parse("7d", 7, "a snooze"), Ok(Duration::days(7)));
+        assert_eq!(
+            parse("169h", 7, "a snooze"),
+            Err("a snooze can last at most 7 days".to_string())
+        );
+        assert_eq!(
+            parse("31d", 30, "a pause"),
+            Err("a pause can last at most 30 days".to_string())
+        );
+        // too big for chrono, not a panic
+        assert!(parse("9999999999999999d", 30, "a pause").is_err());
+    }
+
+    #[test]
+    fn bad_durations_are_rejected() {
+        for value in ["", "m", "-5m", "1.5h", "5w", "1h30m"] {
+            assert!(parse(value, 7, "a snooze").is_err(), "{} was accepted", value);
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use chrono::{DateTime, Utc};
+use rusqlite::params;
+
+use crate::db::Database;
+use crate::notifiers::AlertEvent;
+
+// An escalation step waiting for its time, kept in the database so it survives a restart
+#[derive(Debug, Clone)]
+pub struct Escalation {
+    pub incident_id: i64,
+    pub after_mins: u32,
+    pub channel_ids: Vec<String>,
+    pub event: AlertEvent,
+}
+
+pub fn schedule(db: &Database, due_at: DateTime<Utc>, escalation: &Escalation) -> Result<(), String> {
+    let channel_ids = serde_json::to_string(&escalation.channel_ids).map_err(|e| e.to_string())?;
+    let event = serde_json::to_string(&escalation.event).map_err(|e| e.to_string())?;
+    db.with_conn(|conn| {
+        conn.execute(
+            "INSERT INTO escalations (incident_id, due_at, after_mins, channel_ids, event)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                escalation.incident_id,
+                due_at.timestamp_millis(),
+                escalation.after_mins,
+                channel_ids,
+                event
+            ],
+        )?;
+        Ok(())
+    })
+}
+
+// Removes and returns the steps due by `now`, oldest first. Rows that no longer parse are dropped.
+pub fn take_due(db: &Database, now: DateTime<Utc>) -> Result<Vec<Escalation>, String> {
+    let rows: Vec<(i64, u32, String, String)> = db.with_conn(|conn| {
+        let tx = conn.transaction()?;
+        let rows = {
+            let mut stmt = tx.prepare(
+                "SELECT incident_id, after_mins, channel_ids, event FROM escalations
+                 WHERE due_at <= ?1 ORDER BY due_at",
+            )?;
+            let rows = stmt.query_map(params![now.timestamp_millis()], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+            })?;
+            rows.collect::<rusqlite::Result<Vec<_>>>()?
+        };
+        tx.execute("DELETE FROM escalations WHERE due_at <= ?1", params![now.timestamp_millis()])?;
+        tx.commit()?;
+        Ok(rows)
+    })?;
+    Ok(rows
+        .into_iter()
+        .filter_map(|(incident_id, after_mins, channel_ids, event)| {
+            let parsed = serde_json::from_str(&channel_ids)
+                .and_then(|channel_ids| Ok((channel_ids, serde_json::from_str(&event)?)));
+            match parsed {
+                Ok((channel_ids, event)) => Some(Escalation {
+                    incident_id,
+                    after_mins,
+                    channel_ids,
+                    event,
+                }),
+                Err(e) => {
+                    eprintln!("Dropping unreadable escalation for incident {}: {}", incident_id, e);
+                    None
+                }
+            }
+        })
+        .collect())
+}
+
+// Nothing fires before `at`; steps that came due while the machine slept wait until then
+pub fn defer(db: &Database, at: DateTime<Utc>) -> Result<(), String> {
+    db.with_conn(|conn| {
+        conn.execute(
+            "UPDATE escalations SET due_at = ?1 WHERE due_at < ?1",
+            params![at.timestamp_millis()],
+        )?;
+        Ok(())
+    })
+}
+
+// Moves every step earlier, e.g. by however far the wall clock was turned back
+pub fn shift_back(db: &Database, secs: i64) -> Result<(), String> {
+    db.with_conn(|conn| {
+        conn.execute("UPDATE escalations SET due_at = due_at - ?1", params![secs * 1000])?;
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+    use std::path::Path;
+
+    fn start() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, 2, 12, 0, 0).unwrap()
+    }
+
+    fn escalation(after_mins: u32) -> Escalation {
+        Escalation {
+            incident_id: 7,
+            after_mins,
+            channel_ids: vec!["ops".to_string()],
+            event: AlertEvent::test(),
+        }
+    }
+
+    #[test]
+    fn steps_come_back_once_due() {
+        let db = Database::open(Path::new(":memory:")).unwrap();
+        schedule(&db, start() + Duration::minutes(5), &escalation(5)).unwrap();
+        schedule(&db, start() + Duration::minutes(15), &escalation(15)).unwrap();
+        assert!(take_due(&db, start() + Duration::minutes(4)).unwrap().is_empty());
+
+        let due = take_due(&db, start() + Duration::minutes(5)).unwrap();
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].after_mins, 5);
+        assert_eq!(due[0].channel_ids, vec!["ops".to_string()]);
+        assert_eq!(due[0].event.monitor.name, "Test monitor");
+        // taken steps are gone
+        assert!(take_due(&db, start() + Duration::minutes(5)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn defer_holds_back_overdue_steps_only() {
+        let db = Database::open(Path::new(":memory:")).unwrap();
+        schedule(&db, start(), &escalation(5)).unwrap();
+        schedule(&db, start() + Duration::minutes(30), &escalation(15)).unwrap();
+        defer(&db, start() + Duration::minutes(1)).unwrap();
+        assert!(take_due(&db, start()).unwrap().is_empty());
+        let due = take_due(&db, start() + Duration::minutes(1)).unwrap();
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].after_mins, 5);
+    }
+
+    #[test]
+    fn shift_back_moves_every_step() {
+        let db = Database::open(Path::new(":memory:")).unwrap();
+        schedule(&db, start() + Duration::minutes(10), &escalation(10)).unwrap();
+        shift_back(&db, 600).unwrap();
+        assert_eq!(take_due(&db, start()).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,108 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+
+use crate::history::CheckRecord;
+
+// Sent as "version" in every payload below; bump on breaking changes. See the README.
+pub const EVENT_SCHEMA_VERSION: u32 = 1;
+
+pub const MONITOR_CHECKED: &str = "monitor_checked";
+pub const MONITOR_STATE_CHANGED: &str = "monitor_state_changed";
+pub const INCIDENT_OPENED: &str = "incident_opened";
+pub const INCIDENT_RESOLVED: &str = "incident_resolved";
+pub const CHECK_CYCLE_COMPLETED: &str = "check_cycle_completed";
+// older events, still sent for existing listeners; prefer check_cycle_completed and incident_resolved
+pub const WEBSITE_CHECK_COMPLETE: &str = "website_check_complete";
+pub const WEBSITE_RECOVERED: &str = "website_recovered";
+
+// One check result, as stored in the check history
+#[derive(Debug, Serialize, Clone)]
+pub struct MonitorChecked {
+    #[serde(flatten)]
+    pub check: CheckRecord,
+    // failed, but not yet enough times in a row to call the monitor down
+    pub awaiting_confirmation: bool,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct MonitorStateChanged {
+    pub monitor_id: String,
+    pub name: String,
+    pub url: String,
+    pub group: Option<String>,
+    pub old_state: String,
+    pub new_state: String,
+    pub reason: Option<String>,
+    pub incident_id: Option<i64>,
+    pub changed_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct IncidentOpened {
+    pub incident_id: i64,
+    // the monitor whose outage opened it; others in the group may join later
+    pub monitor_id: String,
+    pub group: Option<String>,
+    pub error: Option<String>,
+    pub started_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct IncidentResolved {
+    pub incident_id: i64,
+    // the last affected monitor to recover (or be removed)
+    pub monitor_id: String,
+    pub resolved_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct CheckCycleCompleted {
+    pub started_at: DateTime<Utc>,
+    pub completed_at: DateTime<Utc>,
+    // results recorded, including confirmation re-checks
+    pub checked: u32,
+    // results thrown away because the app was offline or the clock jumped
+    pub dropped: u32,
+    // states of all monitors once the cycle finished
+    pub up: u32,
+    pub degraded: u32,
+    pub down: u32,
+    pub maintenance: u32,
+    pub paused: u32,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct WebsiteCheckComplete {
+    pub message: String,
+}
+
+// A monitor coming back from down, whether or not that ended its incident
+#[derive(Debug, Serialize, Clone)]
+pub struct WebsiteRecovered {
+    pub id: String,
+    pub name: String,
+    pub url: String,
+    pub down_since: DateTime<Utc>,
+    pub recovered_at: DateTime<Utc>,
+    pub duration_secs: i64,
+    pub last_error: Option<String>,
+}
+
+// Broadcasts to every window (and Rust listeners), so nothing is lost while the window is
+// hidden and nothing panics once it is gone
+pub fn emit<T: Serialize>(app_handle: &AppHandle, event: &str, payload: &T) {
+    let mut value = match serde_json::to_value(payload) {
+        Ok(value) => value,
+        Err(e) => {
+            eprintln!("Failed to serialise {} event: {}", event, e);
+            return;
+        }
+    };
+    if let Some(object) = value.as_object_mut() {
+        object.insert("version".to_string(), EVENT_SCHEMA_VERSION.into());
+    }
+    if let Err(e) = app_handle.emit(event, value) {
+        eprintln!("Failed to emit {} event: {}", event, e);
+    }
+}
@@ -0,0 +1,312 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::checker::CheckResult;
+use crate::monitors::{Confirmation, FlapDetection, LatencyThresholds};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthState {
+    #[default]
+    Up,
+    // responding, but slower than the monitor's warning threshold allows
+    Degraded,
+    Down,
+    // inside a maintenance window; checks still run but nothing alerts
+    Maintenance,
+}
+
+impl HealthState {
+    pub fn label(&self) -> &'static str {
+        match self {
+            HealthState::Up => "up",
+            HealthState::Degraded => "degraded",
+            HealthState::Down => "down",
+            HealthState::Maintenance => "maintenance",
+        }
+    }
+}
+
+// Health of a single check result, plus a human readable reason when it isn't Up
+pub fn evaluate(result: &CheckResult, thresholds: &LatencyThresholds) -> (HealthState, Option<String>) {
+    if let Some(error) = &result.error {
+        return (HealthState::Down, Some(format!("{}: {}", error.class.describe(), error.message)));
+    }
+    let latency = result.response_time_ms;
+    // too slow to be of use, so it counts as a failure
+    if let Some(critical) = thresholds.critical_ms.filter(|c| latency >= *c) {
+        return (
+            HealthState::Down,
+            Some(format!("response time {} ms is over the critical threshold of {} ms", latency, critical)),
+        );
+    }
+    if let Some(warning) = thresholds.warning_ms.filter(|w| latency >= *w) {
+        return (
+            HealthState::Degraded,
+            Some(format!("response time {} ms is over the warning threshold of {} ms", latency, warning)),
+        );
+    }
+    (HealthState::Up, None)
+}
+
+// Turns raw per-check observations into confirmed state changes and spots flapping
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct HealthTracker {
+    pub consecutive_failures: u32,
+    pub consecutive_successes: u32,
+    pub flapping: bool,
+    #[serde(skip)]
+    changes: Vec<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Assessment {
+    pub state: HealthState,
+    // a failure was seen but not enough of them yet to call the monitor down
+    pub awaiting_confirmation: bool,
+    pub flapping_started: bool,
+    pub flapping_ended: bool,
+}
+
+impl HealthTracker {
+    pub fn observe(
+        &mut self,
+        current: HealthState,
+        observed: HealthState,
+        confirmation: &Confirmation,
+        flap_detection: &FlapDetection,
+        now: DateTime<Utc>,
+    ) -> Assessment {
+        if observed == HealthState::Down {
+            self.consecutive_failures += 1;
+            self.consecutive_successes = 0;
+        } else {
+            self.consecutive_successes += 1;
+            self.consecutive_failures = 0;
+        }
+
+        let state = match (current, observed) {
+            (HealthState::Down, HealthState::Down) => HealthState::Down,
+            (_, HealthState::Down) if self.consecutive_failures >= confirmation.failures_before_down => {
+                HealthState::Down
+            }
+            (_, HealthState::Down) => current,
+            (HealthState::Down, _) if self.consecutive_successes < confirmation.successes_before_up => {
+                HealthState::Down
+            }
+            // moving between up and degraded needs no confirmation
+            (_, observed) => observed,
+        };
+        let awaiting_confirmation = observed == HealthState::Down && state != HealthState::Down;
+
+        let was_flapping = self.flapping;
+        if state != current {
+            self.changes.push(now);
+        }
+        match flap_detection.max_changes {
+            Some(max_changes) => {
+                let window_start = now - chrono::Duration::seconds(flap_detection.window_secs as i64);
+                self.changes.retain(|t| *t >= window_start);
+                let changes = self.changes.len() as u32;
+                if changes >= max_changes {
+                    self.flapping = true;
+                } else if changes < max_changes.div_ceil(2) {
+                    // hysteresis so a monitor right on the limit doesn't bounce in and out of flapping
+                    self.flapping = false;
+                }
+            }
+            None => {
+                self.changes.clear();
+                self.flapping = false;
+            }
+        }
+
+        Assessment {
+            state,
+            awaiting_confirmation,
+            flapping_started: self.flapping && !was_flapping,
+            flapping_ended: !self.flapping && was_flapping,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+
+    fn confirmation(failures_before_down: u32, successes_before_up: u32) -> Confirmation {
+        Confirmation {
+            failures_before_down,
+            successes_before_up,
+            retry_delay_secs: None,
+        }
+    }
+
+    fn start() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, 2, 12, 0, 0).unwrap()
+    }
+
+    fn result(response_time_ms: u64, error: Option<&str>) -> CheckResult {
+        serde_json::from_value(serde_json::json!({
+            "checked_at": "2024-01-02T12:00:00Z",
+            "status_code": 200,
+            "response_time_ms": response_time_ms,
+            "response_size": 512,
+            "error": error.map(|message| serde_json::json!({ "class": "timeout", "message": message })),
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn slow_responses_are_degraded_then_down() {
+        let thresholds = LatencyThresholds {
+            warning_ms: Some(500),
+            critical_ms: Some(2000),
+        };
+        assert_eq!(evaluate(&result(499, None), &thresholds), (HealthState::Up, None));
+        let (state, reason) = evaluate(&result(500, None), &thresholds);
+        assert_eq!(state, HealthState::Degraded);
+        assert!(reason.unwrap().contains("warning threshold of 500 ms"));
+        let (state, reason) = evaluate(&result(2000, None), &thresholds);
+        assert_eq!(state, HealthState::Down);
+        assert!(reason.unwrap().contains("critical threshold of 2000 ms"));
+        assert_eq!(evaluate(&result(60_000, None), &LatencyThresholds::default()).0, HealthState::Up);
+    }
+
+    #[test]
+    fn errors_are_down_whatever_the_latency() {
+        let (state, reason) = evaluate(&result(10, Some("timed out")), &LatencyThresholds::default());
+        assert_eq!(state, HealthState::Down);
+        assert!(reason.unwrap().ends_with("timed out"));
+    }
+
+    #[test]
+    fn down_needs_the_configured_failures_in_a_row() {
+        let mut tracker = HealthTracker::default();
+        let confirm = confirmation(3, 1);
+        let flaps = FlapDetection::default();
+        let mut state = HealthState::Up;
+        for _ in 0..2 {
+            let assessment = tracker.observe(state, HealthState::Down, &confirm, &flaps, start());
+            assert_eq!(assessment.state, HealthState::Up);
+            assert!(assessment.awaiting_confirmation);
+            state = assessment.state;
+        }
+        let assessment = tracker.observe(state, HealthState::Down, &confirm, &flaps, start());
+        assert_eq!(assessment.state, HealthState::Down);
+        assert!(!assessment.awaiting_confirmation);
+    }
+
+    #[test]
+    fn a_success_resets_the_failure_count() {
+        let mut tracker = HealthTracker::default();
+        let confirm = confirmation(2, 1);
+        let flaps = FlapDetection::default();
+        tracker.observe(HealthState::Up, HealthState::Down, &confirm, &flaps, start());
+        tracker.observe(HealthState::Up, HealthState::Up, &confirm, &flaps, start());
+        let assessment = tracker.observe(HealthState::Up, HealthState::Down, &confirm, &flaps, start());
+        assert_eq!(assessment.state, HealthState::Up);
+        assert_eq!(tracker.consecutive_failures, 1);
+    }
+
+    #[test]
+    fn up_needs_the_configured_successes_in_a_row() {
+        let mut tracker = HealthTracker::default();
+        let confirm = confirmation(1, 2);
+        let flaps = FlapDetection::default();
+        let assessment = tracker.observe(HealthState::Down, HealthState::Up, &confirm, &flaps, start());
+        assert_eq!(assessment.state, HealthState::Down);
+        assert!(!assessment.awaiting_confirmation);
+        let assessment = tracker.observe(HealthState::Down, HealthState::Degraded, &confirm, &flaps, start());
+        assert_eq!(assessment.state, HealthState::Degraded);
+    }
+
+    #[test]
+    fn up_and_degraded_switch_without_confirmation() {
+        let mut tracker = HealthTracker::default();
+        let confirm = confirmation(3, 3);
+        let flaps = FlapDetection::default();
+        let assessment = tracker.observe(HealthState::Up, HealthState::Degraded, &confirm, &flaps, start());
+        assert_eq!(assessment.state, HealthState::Degraded);
+        let assessment = tracker.observe(HealthState::Degraded, HealthState::Up, &confirm, &flaps, start());
+        assert_eq!(assessment.state, HealthState::Up);
+    }
+
+    // Alternates up and down once a minute and returns each assessment
+    fn alternate(
+        tracker: &mut HealthTracker,
+        flaps: &FlapDetection,
+        from: DateTime<Utc>,
+        count: usize,
+    ) -> Vec<Assessment> {
+        let confirm = confirmation(1, 1);
+        let mut state = HealthState::Up;
+        (0..count)
+            .map(|i| {
+                let observed = if state == HealthState::Up { HealthState::Down } else { HealthState::Up };
+                let at = from + Duration::minutes(i as i64);
+                let assessment = tracker.observe(state, observed, &confirm, flaps, at);
+                state = assessment.state;
+                assessment
+            })
+            .collect()
+    }
+
+    #[test]
+    fn flapping_starts_at_max_changes() {
+        let mut tracker = HealthTracker::default();
+        let flaps = FlapDetection {
+            max_changes: Some(4),
+            window_secs: 600,
+        };
+        let assessments = alternate(&mut tracker, &flaps, start(), 4);
+        assert!(assessments[..3].iter().all(|a| !a.flapping_started));
+        assert!(assessments[3].flapping_started);
+        assert!(tracker.flapping);
+    }
+
+    #[test]
+    fn flapping_ends_only_below_half_of_max_changes() {
+        let mut tracker = HealthTracker::default();
+        let flaps = FlapDetection {
+            max_changes: Some(4),
+            window_secs: 600,
+        };
+        alternate(&mut tracker, &flaps, start(), 4);
+        assert!(tracker.flapping);
+        let confirm = confirmation(1, 1);
+
+        // changes at minutes 0-3; at minute 12 only those at 2 and 3 are in the window, which
+        // is half the limit and not enough to end it
+        let at = start() + Duration::minutes(12);
+        let assessment = tracker.observe(HealthState::Up, HealthState::Up, &confirm, &flaps, at);
+        assert!(!assessment.flapping_ended);
+        assert!(tracker.flapping);
+
+        // at minute 13 only the change at 3 is left
+        let at = start() + Duration::minutes(13);
+        let assessment = tracker.observe(HealthState::Up, HealthState::Up, &confirm, &flaps, at);
+        assert!(assessment.flapping_ended);
+        assert!(!tracker.flapping);
+    }
+
+    #[test]
+    fn turning_flap_detection_off_ends_flapping() {
+        let mut tracker = HealthTracker::default();
+        let flaps = FlapDetection {
+            max_changes: Some(2),
+            window_secs: 600,
+        };
+        alternate(&mut tracker, &flaps, start(), 2);
+        assert!(tracker.flapping);
+        let assessment = tracker.observe(
+            HealthState::Up,
+            HealthState::Up,
+            &confirmation(1, 1),
+            &FlapDetection::default(),
+            start() + Duration::minutes(3),
+        );
+        assert!(assessment.flapping_ended);
+    }
+}
@@ -0,0 +1,209 @@
+use chrono::{DateTime, Utc};
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+
+use crate::db::Database;
+
+const DEFAULT_PAGE_SIZE: u32 = 100;
+const MAX_PAGE_SIZE: u32 = 1000;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CheckRecord {
+    pub monitor_id: String,
+    pub checked_at: DateTime<Utc>,
+    // the monitor's confirmed state after this check
+    pub state: String,
+    // what this check on its own saw
+    pub observed_state: String,
+    pub status_code: Option<u16>,
+    pub latency_ms: u64,
+    pub response_size: Option<u64>,
+    pub error_class: Option<String>,
+    pub error_message: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HistoryPage {
+    pub total: u64,
+    pub offset: u32,
+    pub limit: u32,
+    pub entries: Vec<CheckRecord>,
+}
+
+pub fn record(db: &Database, record: &CheckRecord) -> Result<(), String> {
+    db.with_conn(|conn| {
+        conn.execute(
+            "INSERT INTO checks (monitor_id, checked_at, state, observed_state, status_code, latency_ms,
+                response_size, error_class, error_message)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![
+                record.monitor_id,
+                record.checked_at.timestamp_millis(),
+                record.state,
+                record.observed_state,
+                record.status_code,
+                record.latency_ms as i64,
+                record.response_size.map(|s| s as i64),
+                record.error_class,
+                record.error_message,
+            ],
+        )
+        .map(|_| ())
+    })
+}
+
+// Newest first. `from` is inclusive and `to` exclusive; either may be left open.
+pub fn query(
+    db: &Database,
+    monitor_id: &str,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    limit: Option<u32>,
+    offset: Option<u32>,
+) -> Result<HistoryPage, String> {
+    let from_ms = from.map(|t| t.timestamp_millis()).unwrap_or(i64::MIN);
+    let to_ms = to.map(|t| t.timestamp_millis()).unwrap_or(i64::MAX);
+    let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    let offset = offset.unwrap_or(0);
+
+    db.with_conn(|conn| {
+        let total: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM checks WHERE monitor_id = ?1 AND checked_at >= ?2 AND checked_at < ?3",
+            params![monitor_id, from_ms, to_ms],
+            |row| row.get(0),
+        )?;
+        let mut stmt = conn.prepare(
+            "SELECT monitor_id, checked_at, state, observed_state, status_code, latency_ms, response_size,
+                    error_class, error_message
+             FROM checks
+             WHERE monitor_id = ?1 AND checked_at >= ?2 AND checked_at < ?3
+             ORDER BY checked_at DESC, id DESC
+             LIMIT ?4 OFFSET ?5",
+        )?;
+        let entries = stmt
+            .query_map(params![monitor_id, from_ms, to_ms, limit, offset], |row| {
+                Ok(CheckRecord {
+                    monitor_id: row.get(0)?,
+                    checked_at: DateTime::from_timestamp_millis(row.get(1)?).unwrap_or_default(),
+                    state: row.get(2)?,
+                    observed_state: row.get(3)?,
+                    status_code: row.get(4)?,
+                    latency_ms: row.get::<_, i64>(5)? as u64,
+                    response_size: row.get::<_, Option<i64>>(6)?.map(|s| s as u64),
+                    error_class: row.get(7)?,
+                    error_message: row.get(8)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(HistoryPage {
+            total: total as u64,
+            offset,
+            limit,
+            entries,
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+    use std::path::Path;
+
+    fn at(mins: i64) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, 2, 12, 0, 0).unwrap() + Duration::minutes(mins)
+    }
+
+    fn check(monitor_id: &str, mins: i64) -> CheckRecord {
+        CheckRecord {
+            monitor_id: monitor_id.to_string(),
+            checked_at: at(mins),
+            state: "up".to_string(),
+            observed_state: "up".to_string(),
+            status_code: Some(200),
+            latency_ms: mins as u64,
+            response_size: Some(512),
+            error_class: None,
+            error_message: None,
+        }
+    }
+
+    // a database holding checks of "a" at minutes 0..10 and one of "b"
+    fn database() -> Database {
+        let db = Database::open(Path::new(":memory:")).unwrap();
+        for mins in 0..10 {
+            record(&db, &check("a", mins)).unwrap();
+        }
+        record(&db, &check("b", 3)).unwrap();
+        db
+    }
+
+    fn minutes(page: &HistoryPage) -> Vec<u64> {
+        page.entries.iter().map(|e| e.latency_ms).collect()
+    }
+
+    #[test]
+    fn records_read_back_as_written() {
+        let db = Database::open(Path::new(":memory:")).unwrap();
+        let mut failed = check("a", 0);
+        failed.state = "down".to_string();
+        failed.observed_state = "down".to_string();
+        failed.status_code = None;
+        failed.response_size = None;
+        failed.error_class = Some("timeout".to_string());
+        failed.error_message = Some("timed out after 10s".to_string());
+        record(&db, &failed).unwrap();
+
+        let page = query(&db, "a", None, None, None, None).unwrap();
+        assert_eq!(page.total, 1);
+        let read = &page.entries[0];
+        assert_eq!(read.checked_at, at(0));
+        assert_eq!((read.state.as_str(), read.observed_state.as_str()), ("down", "down"));
+        assert_eq!((read.status_code, read.response_size), (None, None));
+        assert_eq!(read.error_class.as_deref(), Some("timeout"));
+        assert_eq!(read.error_message.as_deref(), Some("timed out after 10s"));
+    }
+
+    #[test]
+    fn pages_run_newest_first() {
+        let db = database();
+        let first = query(&db, "a", None, None, Some(4), None).unwrap();
+        assert_eq!((first.total, first.limit, first.offset), (10, 4, 0));
+        assert_eq!(minutes(&first), vec![9, 8, 7, 6]);
+        let second = query(&db, "a", None, None, Some(4), Some(4)).unwrap();
+        assert_eq!(minutes(&second), vec![5, 4, 3, 2]);
+        let last = query(&db, "a", None, None, Some(4), Some(8)).unwrap();
+        assert_eq!(minutes(&last), vec![1, 0]);
+        let past_the_end = query(&db, "a", None, None, Some(4), Some(12)).unwrap();
+        assert_eq!((past_the_end.total, past_the_end.entries.len()), (10, 0));
+    }
+
+    #[test]
+    fn page_size_is_clamped() {
+        let db = database();
+        assert_eq!(query(&db, "a", None, None, None, None).unwrap().limit, DEFAULT_PAGE_SIZE);
+        assert_eq!(query(&db, "a", None, None, Some(0), None).unwrap().limit, 1);
+        assert_eq!(query(&db, "a", None, None, Some(5000), None).unwrap().limit, MAX_PAGE_SIZE);
+    }
+
+    #[test]
+    fn queries_filter_by_monitor_and_half_open_range() {
+        let db = database();
+        let b = query(&db, "b", None, None, None, None).unwrap();
+        assert_eq!((b.total, minutes(&b)), (1, vec![3]));
+        let range = query(&db, "a", Some(at(2)), Some(at(5)), None, None).unwrap();
+        assert_eq!((range.total, minutes(&range)), (3, vec![4, 3, 2]));
+        let from = query(&db, "a", Some(at(8)), None, None, None).unwrap();
+        assert_eq!(minutes(&from), vec![9, 8]);
+        assert_eq!(query(&db, "missing", None, None, None, None).unwrap().total, 0);
+    }
+
+    #[test]
+    fn checks_at_the_same_time_keep_insert_order() {
+        let db = Database::open(Path::new(":memory:")).unwrap();
+        for latency_ms in [1, 2, 3] {
+            record(&db, &CheckRecord { latency_ms, ..check("a", 0) }).unwrap();
+        }
+        assert_eq!(minutes(&query(&db, "a", None, None, None, None).unwrap()), vec![3, 2, 1]);
+    }
+}
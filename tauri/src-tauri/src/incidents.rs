@@ -0,0 +1,420 @@
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::db::Database;
+
+const DEFAULT_PAGE_SIZE: u32 = 50;
+const MAX_PAGE_SIZE: u32 = 500;
+
+#[derive(Debug, Serialize, Clone)]
+pub struct Incident {
+    pub id: i64,
+    // open, acknowledged or resolved
+    pub status: String,
+    // monitors in the same group share one incident while it is open
+    pub group: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub ended_at: Option<DateTime<Utc>>,
+    pub duration_secs: i64,
+    pub first_error: Option<String>,
+    pub last_error: Option<String>,
+    pub acknowledged_by: Option<String>,
+    pub acknowledged_at: Option<DateTime<Utc>>,
+    pub monitors: Vec<AffectedMonitor>,
+    pub notes: Vec<IncidentNote>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct AffectedMonitor {
+    pub monitor_id: String,
+    pub joined_at: DateTime<Utc>,
+    pub recovered_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct IncidentNote {
+    pub id: i64,
+    pub author: String,
+    pub created_at: DateTime<Utc>,
+    pub body: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct IncidentPage {
+    pub total: u64,
+    pub offset: u32,
+    pub limit: u32,
+    pub incidents: Vec<Incident>,
+}
+
+fn from_ms(ms: i64) -> DateTime<Utc> {
+    DateTime::from_timestamp_millis(ms).unwrap_or_default()
+}
+
+// Called on a confirmed down transition. Joins an unresolved incident for the same group if
+// there is one, otherwise opens a new incident. Returns the incident id and whether it is new.
+pub fn open_or_join(
+    db: &Database,
+    monitor_id: &str,
+    group: Option<&str>,
+    error: Option<&str>,
+    at: DateTime<Utc>,
+) -> Result<(i64, bool), String> {
+    let at_ms = at.timestamp_millis();
+    db.with_conn(|conn| {
+        let tx = conn.transaction()?;
+        let existing: Option<i64> = match group {
+            Some(group) => tx
+                .query_row(
+                    "SELECT id FROM incidents WHERE group_name = ?1 AND status != 'resolved'
+                     ORDER BY started_at DESC LIMIT 1",
+                    params![group],
+                    |row| row.get(0),
+                )
+                .optional()?,
+            None => None,
+        };
+        let incident_id = match existing {
+            Some(id) => {
+                tx.execute(
+                    "UPDATE incidents SET last_error = COALESCE(?2, last_error) WHERE id = ?1",
+                    params![id, error],
+                )?;
+                id
+            }
+            None => {
+                tx.execute(
+                    "INSERT INTO incidents (status, group_name, started_at, first_error, last_error)
+                     VALUES ('open', ?1, ?2, ?3, ?3)",
+                    params![group, at_ms, error],
+                )?;
+                tx.last_insert_rowid()
+            }
+        };
+        tx.execute(
+            "INSERT INTO incident_monitors (incident_id, monitor_id, joined_at) VALUES (?1, ?2, ?3)
+             ON CONFLICT(incident_id, monitor_id) DO UPDATE SET joined_at = excluded.joined_at, recovered_at = NULL",
+            params![incident_id, monitor_id, at_ms],
+        )?;
+        tx.commit()?;
+        Ok((incident_id, existing.is_none()))
+    })
+}
+
+pub fn record_error(db: &Database, incident_id: i64, error: &str) -> Result<(), String> {
+    db.with_conn(|conn| {
+        conn.execute(
+            "UPDATE incidents SET last_error = ?2 WHERE id = ?1",
+            params![incident_id, error],
+        )
+        .map(|_| ())
+    })
+}
+
+// Marks the monitor recovered; the incident resolves once every affected monitor has.
+// Returns true if this resolved the incident.
+pub fn monitor_recovered(db: &Database, incident_id: i64, monitor_id: &str, at: DateTime<Utc>) -> Result<bool, String> {
+    let at_ms = at.timestamp_millis();
+    db.with_conn(|conn| {
+        let tx = conn.transaction()?;
+        tx.execute(
+            "UPDATE incident_monitors SET recovered_at = ?3 WHERE incident_id = ?1 AND monitor_id = ?2",
+            params![incident_id, monitor_id, at_ms],
+        )?;
+        let still_down: i64 = tx.query_row(
+            "SELECT COUNT(*) FROM incident_monitors WHERE incident_id = ?1 AND recovered_at IS NULL",
+            params![incident_id],
+            |row| row.get(0),
+        )?;
+        let resolved = still_down == 0
+            && tx.execute(
+                "UPDATE incidents SET status = 'resolved', ended_at = ?2 WHERE id = ?1 AND status != 'resolved'",
+                params![incident_id, at_ms],
+            )? > 0;
+        tx.commit()?;
+        Ok(resolved)
+    })
+}
+
+pub fn acknowledge(db: &Database, incident_id: i64, user: &str, note: Option<&str>) -> Result<Incident, String> {
+    let now = Utc::now();
+    db.with_conn(|conn| {
+        let tx = conn.transaction()?;
+        let status: Option<String> = tx
+            .query_row("SELECT status FROM incidents WHERE id = ?1", params![incident_id], |row| row.get(0))
+            .optional()?;
+        if status.as_deref() == Some("open") {
+            tx.execute(
+                "UPDATE incidents SET status = 'acknowledged', acknowledged_by = ?2, acknowledged_at = ?3 WHERE id = ?1",
+                params![incident_id, user, now.timestamp_millis()],
+            )?;
+        }
+        if let Some(note) = note.map(str::trim).filter(|n| !n.is_empty()) {
+            insert_note(&tx, incident_id, user, note, now)?;
+        }
+        tx.commit()
+    })?;
+    get(db, incident_id)?.ok_or_else(|| format!("no incident with id {}", incident_id))
+}
+
+pub fn add_note(db: &Database, incident_id: i64, user: &str, body: &str) -> Result<Incident, String> {
+    let body = body.trim();
+    if body.is_empty() {
+        return Err("note must not be empty".to_string());
+    }
+    if get(db, incident_id)?.is_none() {
+        return Err(format!("no incident with id {}", incident_id));
+    }
+    db.with_conn(|conn| insert_note(conn, incident_id, user, body, Utc::now()))?;
+    get(db, incident_id)?.ok_or_else(|| format!("no incident with id {}", incident_id))
+}
+
+fn insert_note(conn: &Connection, incident_id: i64, author: &str, body: &str, at: DateTime<Utc>) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO incident_notes (incident_id, author, created_at, body) VALUES (?1, ?2, ?3, ?4)",
+        params![incident_id, author, at.timestamp_millis(), body],
+    )
+    .map(|_| ())
+}
+
+pub fn get(db: &Database, incident_id: i64) -> Result<Option<Incident>, String> {
+    db.with_conn(|conn| {
+        let incident = conn
+            .query_row(&format!("{} WHERE id = ?1", SELECT_INCIDENT), params![incident_id], incident_from_row)
+            .optional()?;
+        match incident {
+            Some(mut incident) => {
+                load_details(conn, &mut incident)?;
+                Ok(Some(incident))
+            }
+            None => Ok(None),
+        }
+    })
+}
+
+// Newest first, optionally filtered by status, monitor and start time ([from, to))
+pub fn list(
+    db: &Database,
+    status: Option<&str>,
+    monitor_id: Option<&str>,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    limit: Option<u32>,
+    offset: Option<u32>,
+) -> Result<IncidentPage, String> {
+    let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    let offset = offset.unwrap_or(0);
+    let from_ms = from.map(|t| t.timestamp_millis()).unwrap_or(i64::MIN);
+    let to_ms = to.map(|t| t.timestamp_millis()).unwrap_or(i64::MAX);
+    let filter = "WHERE (?1 IS NULL OR status = ?1)
+          AND (?2 IS NULL OR id IN (SELECT incident_id FROM incident_monitors WHERE monitor_id = ?2))
+          AND started_at >= ?3 AND started_at < ?4";
+
+    db.with_conn(|conn| {
+        let total: i64 = conn.query_row(
+            &format!("SELECT COUNT(*) FROM incidents {}", filter),
+            params![status, monitor_id, from_ms, to_ms],
+            |row| row.get(0),
+        )?;
+        let mut stmt = conn.prepare(&format!(
+            "{} {} ORDER BY started_at DESC, id DESC LIMIT ?5 OFFSET ?6",
+            SELECT_INCIDENT, filter
+        ))?;
+        let mut incidents = stmt
+            .query_map(params![status, monitor_id, from_ms, to_ms, limit, offset], incident_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        for incident in incidents.iter_mut() {
+            load_details(conn, incident)?;
+        }
+        Ok(IncidentPage {
+            total: total as u64,
+            offset,
+            limit,
+            incidents,
+        })
+    })
+}
+
+// Unresolved incidents keyed by monitor, so state can be restored after a restart
+pub fn unresolved_by_monitor(db: &Database) -> Result<HashMap<String, Incident>, String> {
+    let page = db.with_conn(|conn| {
+        let mut stmt = conn.prepare(&format!("{} WHERE status != 'resolved'", SELECT_INCIDENT))?;
+        let mut incidents = stmt
+            .query_map([], incident_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        for incident in incidents.iter_mut() {
+            load_details(conn, incident)?;
+        }
+        Ok(incidents)
+    })?;
+    let mut by_monitor = HashMap::new();
+    for incident in page {
+        for monitor in incident.monitors.iter().filter(|m| m.recovered_at.is_none()) {
+            by_monitor.insert(monitor.monitor_id.clone(), incident.clone());
+        }
+    }
+    Ok(by_monitor)
+}
+
+const SELECT_INCIDENT: &str = "SELECT id, status, group_name, started_at, ended_at, first_error, last_error,
+        acknowledged_by, acknowledged_at FROM incidents";
+
+fn incident_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Incident> {
+    let started_at = from_ms(row.get(3)?);
+    let ended_at = row.get::<_, Option<i64>>(4)?.map(from_ms);
+    Ok(Incident {
+        id: row.get(0)?,
+        status: row.get(1)?,
+        group: row.get(2)?,
+        started_at,
+        ended_at,
+        duration_secs: (ended_at.unwrap_or_else(Utc::now) - started_at).num_seconds().max(0),
+        first_error: row.get(5)?,
+        last_error: row.get(6)?,
+        acknowledged_by: row.get(7)?,
+        acknowledged_at: row.get::<_, Option<i64>>(8)?.map(from_ms),
+        monitors: Vec::new(),
+        notes: Vec::new(),
+    })
+}
+
+fn load_details(conn: &Connection, incident: &mut Incident) -> rusqlite::Result<()> {
+    let mut stmt = conn.prepare(
+        "SELECT monitor_id, joined_at, recovered_at FROM incident_monitors WHERE incident_id = ?1 ORDER BY joined_at",
+    )?;
+    incident.monitors = stmt
+        .query_map(params![incident.id], |row| {
+            Ok(AffectedMonitor {
+                monitor_id: row.get(0)?,
+                joined_at: from_ms(row.get(1)?),
+                recovered_at: row.get::<_, Option<i64>>(2)?.map(from_ms),
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let mut stmt = conn.prepare(
+        "SELECT id, author, created_at, body FROM incident_notes WHERE incident_id = ?1 ORDER BY created_at",
+    )?;
+    incident.notes = stmt
+        .query_map(params![incident.id], |row| {
+            Ok(IncidentNote {
+                id: row.get(0)?,
+                author: row.get(1)?,
+                created_at: from_ms(row.get(2)?),
+                body: row.get(3)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+    use std::path::Path;
+
+    fn at(mins: i64) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, 2, 12, 0, 0).unwrap() + Duration::minutes(mins)
+    }
+
+    fn incident(db: &Database, id: i64) -> Incident {
+        get(db, id).unwrap().unwrap()
+    }
+
+    #[test]
+    fn a_down_monitor_opens_an_incident_that_resolves_on_recovery() {
+        let db = Database::open(Path::new(":memory:")).unwrap();
+        let (id, new) = open_or_join(&db, "a", None, Some("timed out"), at(0)).unwrap();
+        assert!(new);
+        let open = incident(&db, id);
+        assert_eq!(open.status, "open");
+        assert_eq!(open.first_error.as_deref(), Some("timed out"));
+        assert_eq!(open.monitors.len(), 1);
+
+        record_error(&db, id, "connection refused").unwrap();
+        assert!(monitor_recovered(&db, id, "a", at(5)).unwrap());
+        let resolved = incident(&db, id);
+        assert_eq!(resolved.status, "resolved");
+        assert_eq!(resolved.ended_at, Some(at(5)));
+        assert_eq!(resolved.duration_secs, 300);
+        assert_eq!(resolved.first_error.as_deref(), Some("timed out"));
+        assert_eq!(resolved.last_error.as_deref(), Some("connection refused"));
+        assert_eq!(resolved.monitors[0].recovered_at, Some(at(5)));
+    }
+
+    #[test]
+    fn monitors_in_a_group_share_an_incident_until_all_recover() {
+        let db = Database::open(Path::new(":memory:")).unwrap();
+        let (id, _) = open_or_join(&db, "a", Some("web"), None, at(0)).unwrap();
+        let (joined, new) = open_or_join(&db, "b", Some("web"), Some("502"), at(1)).unwrap();
+        assert_eq!((joined, new), (id, false));
+        assert_eq!(incident(&db, id).last_error.as_deref(), Some("502"));
+
+        // ungrouped monitors and other groups get their own
+        assert_ne!(open_or_join(&db, "c", None, None, at(1)).unwrap().0, id);
+        assert_ne!(open_or_join(&db, "d", Some("api"), None, at(1)).unwrap().0, id);
+
+        assert!(!monitor_recovered(&db, id, "a", at(2)).unwrap());
+        assert_eq!(incident(&db, id).status, "open");
+        assert!(monitor_recovered(&db, id, "b", at(3)).unwrap());
+        // a resolved incident isn't joined again
+        assert_ne!(open_or_join(&db, "a", Some("web"), None, at(4)).unwrap().0, id);
+    }
+
+    #[test]
+    fn a_monitor_that_goes_down_again_rejoins_from_that_time() {
+        let db = Database::open(Path::new(":memory:")).unwrap();
+        let (id, _) = open_or_join(&db, "a", Some("web"), None, at(0)).unwrap();
+        open_or_join(&db, "b", Some("web"), None, at(1)).unwrap();
+        monitor_recovered(&db, id, "b", at(2)).unwrap();
+        open_or_join(&db, "b", Some("web"), None, at(10)).unwrap();
+
+        let monitors = incident(&db, id).monitors;
+        let b = monitors.iter().find(|m| m.monitor_id == "b").unwrap();
+        assert_eq!(b.joined_at, at(10));
+        assert_eq!(b.recovered_at, None);
+        assert_eq!(unresolved_by_monitor(&db).unwrap()["b"].id, id);
+    }
+
+    #[test]
+    fn acknowledging_records_who_and_an_optional_note() {
+        let db = Database::open(Path::new(":memory:")).unwrap();
+        let (id, _) = open_or_join(&db, "a", None, None, at(0)).unwrap();
+        let acknowledged = acknowledge(&db, id, "sam", Some(" looking into it ")).unwrap();
+        assert_eq!(acknowledged.status, "acknowledged");
+        assert_eq!(acknowledged.acknowledged_by.as_deref(), Some("sam"));
+        assert_eq!(acknowledged.notes.len(), 1);
+        assert_eq!(acknowledged.notes[0].body, "looking into it");
+
+        // a second acknowledgement keeps the first one's author
+        let again = acknowledge(&db, id, "kim", None).unwrap();
+        assert_eq!(again.acknowledged_by.as_deref(), Some("sam"));
+        assert_eq!(again.notes.len(), 1);
+
+        assert!(monitor_recovered(&db, id, "a", at(5)).unwrap());
+        assert_eq!(incident(&db, id).status, "resolved");
+        assert!(acknowledge(&db, 999, "sam", None).is_err());
+        assert!(add_note(&db, id, "sam", "  ").is_err());
+    }
+
+    #[test]
+    fn lists_filter_by_status_and_monitor_newest_first() {
+        let db = Database::open(Path::new(":memory:")).unwrap();
+        let (first, _) = open_or_join(&db, "a", None, None, at(0)).unwrap();
+        let (second, _) = open_or_join(&db, "b", None, None, at(1)).unwrap();
+        monitor_recovered(&db, first, "a", at(2)).unwrap();
+
+        let all = list(&db, None, None, None, None, None, None).unwrap();
+        assert_eq!(all.total, 2);
+        assert_eq!(all.incidents.iter().map(|i| i.id).collect::<Vec<_>>(), vec![second, first]);
+        let open = list(&db, Some("open"), None, None, None, None, None).unwrap();
+        assert_eq!(open.incidents.iter().map(|i| i.id).collect::<Vec<_>>(), vec![second]);
+        let for_a = list(&db, None, Some("a"), None, None, None, None).unwrap();
+        assert_eq!(for_a.incidents.iter().map(|i| i.id).collect::<Vec<_>>(), vec![first]);
+        let page = list(&db, None, None, None, None, Some(1), Some(1)).unwrap();
+        assert_eq!((page.total, page.incidents[0].id), (2, first));
+    }
+}
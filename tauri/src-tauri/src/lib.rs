@@ -1,33 +1,113 @@
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
 
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::sync::{Mutex, Arc};
 use tokio::sync::watch;
+use tokio::task::JoinSet;
 use tauri::{
     async_runtime,
-    menu::{Menu, MenuItem},
-    tray::{MouseButton, MouseButtonState, TrayIcon, TrayIconBuilder, TrayIconEvent},
-    AppHandle, Emitter, Manager,
+    AppHandle, Manager,
 };
-use tokio::time::{interval, Duration};
+use tokio::time::Duration;
 use tauri_plugin_notification::NotificationExt;
 use tauri_plugin_keyring::KeyringExt;
 
+mod assertions;
 mod auth;
+mod checker;
+mod clock;
+mod config;
+mod db;
+mod durations;
+mod escalations;
+mod events;
+mod health;
+mod history;
+mod incidents;
+mod maintenance;
+mod monitors;
+mod network;
+mod mutes;
+mod notifiers;
+mod pauses;
+mod routing;
+mod scheduler;
+mod settings;
+mod tls;
+mod tray;
+mod uptime;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 struct Website {
-    url: String,
-    is_down: bool,
+    #[serde(flatten)]
+    monitor: monitors::Monitor,
+    state: health::HealthState,
+    // why the monitor isn't up, if it isn't
+    state_reason: Option<String>,
+    // when the current outage was confirmed, and the latest error seen during it
+    down_since: Option<DateTime<Utc>>,
+    last_error: Option<String>,
+    // the incident this monitor's current outage belongs to
+    incident_id: Option<i64>,
     last_checked: String,
+    last_result: Option<checker::CheckResult>,
+    certificate: Option<tls::CertificateInfo>,
+    certificate_alerts: tls::CertificateAlertState,
+    tracker: health::HealthTracker,
+    // acknowledged: no notifications until the monitor recovers
     notification_cleared: bool,
+    snoozed_until: Option<DateTime<Utc>>,
 }
 
+impl Website {
+    fn new(monitor: monitors::Monitor) -> Website {
+        Website {
+            monitor,
+            state: health::HealthState::Up,
+            state_reason: None,
+            down_since: None,
+            last_error: None,
+            incident_id: None,
+            last_checked: String::new(),
+            last_result: None,
+            certificate: None,
+            certificate_alerts: tls::CertificateAlertState::default(),
+            tracker: health::HealthTracker::default(),
+            notification_cleared: false,
+            snoozed_until: None,
+        }
+    }
+
+    fn mute_state(&self) -> mutes::MuteState {
+        mutes::MuteState {
+            acknowledged: self.notification_cleared,
+            snoozed_until: self.snoozed_until,
+        }
+    }
+}
+
+// The refresher re-reads the token expiry at least this often
+const MAX_REFRESH_WAIT_SECS: u64 = 15 * 60;
+const REFRESH_RETRY_SECS: u64 = 60;
+
 struct AppState {
     websites: Mutex<Vec<Website>>,
-    tray: TrayIcon,
+    settings: Mutex<settings::AppSettings>,
+    maintenance: Mutex<Vec<maintenance::MaintenanceWindow>>,
+    channels: Mutex<Vec<notifiers::Channel>>,
+    routing_rules: Mutex<Vec<routing::RoutingRule>>,
+    // set while all monitoring is paused
+    paused: Mutex<Option<pauses::GlobalPause>>,
+    network: network::Network,
+    clock: clock::Clock,
+    engine: Mutex<Arc<checker::CheckEngine>>,
+    scheduler: Arc<scheduler::Scheduler>,
+    // None if the history database could not be opened; monitoring carries on without it
+    db: Option<db::Database>,
+    tray: tray::Tray,
     refresher_tx: Mutex<Option<watch::Sender<bool>>>,
+    app_registration: Mutex<Option<auth::AppRegistration>>,
 }
 
 #[tauri::command]
@@ -42,7 +122,7 @@ async fn login(
     let (redirect_uri, rx) = auth::start_auth_server();
 
     let auth_url = format!(
-        "https://login.microsoftonline.com/{}/oauth2/v2.0/authorize?client_id={}&response_type=code&redirect_uri={}&response_mode=query&scope=User.Read%20openid%20profile%20offline_access&code_challenge={}&code_challenge_method=S256",
+        "https://login.microsoftonline.com/{}/oauth2/v2.0/authorize?client_id={}&response_type=code&redirect_uri={}&response_mode=query&scope=User.Read%20Mail.Send%20openid%20profile%20offline_access&code_challenge={}&code_challenge_method=S256",
         tenant_id, client_id, 
         urlencoding::encode(&redirect_uri),
         code_challenge
@@ -68,9 +148,13 @@ async fn login(
     )
     .await
     .map_err(|e| e.to_string())?;
+    if let Some(registration) = auth::AppRegistration::new(&client_id, &tenant_id) {
+        *app_handle.state::<AppState>().app_registration.lock().unwrap() = Some(registration);
+    }
 
     // Attempt to fetch user's profile photo (best-effort)
-    let photo_data_url = match auth::fetch_user_photo(&token.access_token).await {
+    let graph_base_url = auth::graph_base_url(&app_handle);
+    let photo_data_url = match auth::fetch_user_photo(&graph_base_url, &token.access_token).await {
         Ok(Some(url)) => Some(url),
         Ok(None) => None,
         Err(e) => {
@@ -93,42 +177,52 @@ async fn login(
     let state = app_handle.state::<AppState>();
     *state.refresher_tx.lock().unwrap() = Some(tx.clone());
 
+    let mut clock_jumps = state.clock.subscribe();
+
     async_runtime::spawn(async move {
-        // initial attempt to compute sleep until expiry
+        // when the last refresh attempt failed, wait this long before the next one
+        let mut retry_after: Option<u64> = None;
         loop {
             println!("Refresher running");
-            // Try to load stored token and compute sleep until expiry
-            if let Ok(Some(stored)) = auth::load_token_from_keyring(&ah, &*user_for_refresher) {
-                let expires_at = stored.issued_at + stored.token.expires_in;
-                let now = Utc::now().timestamp();
-                // sleep until 60 seconds before expiry, or at most 5 minutes
-                let sleep_secs = if expires_at > now + 60 {
-                    (expires_at - now - 60) as u64
-                } else {
-                    300u64
-                };
-                // wait either for cancel or timeout
-                let sleep = tokio::time::sleep(Duration::from_secs(sleep_secs));
+            // Work out the wait from the wall clock every time round, and cap it, so a clock
+            // change or a suspend can't leave us sleeping past expiry
+            let Ok(Some(stored)) = auth::load_token_from_keyring(&ah, &user_for_refresher) else {
+                // no token stored yet; wait a short while before retrying
                 tokio::select! {
                     _ = rx.changed() => {
-                        // cancelled when value becomes true
-                        if *rx.borrow() {
-                            break;
-                        }
+                        if *rx.borrow() { break; }
                     }
-                    _ = sleep => {
-                        let _ = auth::ensure_valid_token(ah.clone(), &*user_for_refresher, &client_id_clone, &tenant_id_clone, 60).await;
-                        // loop and recompute next sleep
+                    _ = tokio::time::sleep(Duration::from_secs(60)) => {}
+                }
+                continue;
+            };
+            let refresh_at = stored.issued_at + stored.token.expires_in - 60;
+            let wait_secs = retry_after
+                .unwrap_or_else(|| (refresh_at - Utc::now().timestamp()).max(0) as u64)
+                .min(MAX_REFRESH_WAIT_SECS);
+            tokio::select! {
+                _ = rx.changed() => {
+                    // cancelled when value becomes true
+                    if *rx.borrow() {
+                        break;
                     }
                 }
-            } else {
-                // no token stored yet; wait a short while before retrying
-                let sleep = tokio::time::sleep(Duration::from_secs(60));
-                tokio::select! {
-                    _ = rx.changed() => {
-                        if *rx.borrow() { break; }
+                // re-plan the refresh against the new time
+                _ = clock_jumps.changed() => {
+                    retry_after = None;
+                }
+                _ = tokio::time::sleep(Duration::from_secs(wait_secs)) => {
+                    if Utc::now().timestamp() < refresh_at {
+                        // woke early because of the cap; loop and recompute
+                        continue;
                     }
-                    _ = sleep => continue,
+                    retry_after = match auth::ensure_valid_token(ah.clone(), &user_for_refresher, &client_id_clone, &tenant_id_clone, 60).await {
+                        Ok(_) => None,
+                        Err(e) => {
+                            eprintln!("Token refresh failed: {}", e);
+                            Some(REFRESH_RETRY_SECS)
+                        }
+                    };
                 }
             }
         }
@@ -233,13 +327,864 @@ async fn check_websites(
     state: tauri::State<'_, AppState>,
     app_handle: AppHandle,
 ) -> Result<String, String> {
-    let websites_clone = state.websites.lock().unwrap().clone();
-    match do_check_websites(websites_clone, app_handle).await {
-        Ok((updated_websites, message)) => {
-            *state.websites.lock().unwrap() = updated_websites;
-            Ok(message)
+    if state.paused.lock().unwrap().is_some() {
+        return Err("monitoring is paused".to_string());
+    }
+    let websites_clone: Vec<Website> = state
+        .websites
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|w| w.monitor.enabled)
+        .cloned()
+        .collect();
+    // monitors the scheduler is already checking are left to it
+    let websites_clone = state.scheduler.claim(websites_clone);
+    let ids: Vec<String> = websites_clone.iter().map(|w| w.monitor.id.clone()).collect();
+    let result = do_check_websites(websites_clone, app_handle).await;
+    state.scheduler.release(&ids);
+    result
+}
+
+fn save_monitors(app_handle: &AppHandle, websites: &[Website]) -> Result<(), String> {
+    let monitors: Vec<monitors::Monitor> = websites.iter().map(|w| w.monitor.clone()).collect();
+    monitors::save(app_handle, &monitors)
+}
+
+#[tauri::command]
+fn list_websites(state: tauri::State<'_, AppState>) -> Vec<Website> {
+    state.websites.lock().unwrap().clone()
+}
+
+#[tauri::command]
+fn add_website(
+    monitor: monitors::MonitorInput,
+    state: tauri::State<'_, AppState>,
+    app_handle: AppHandle,
+) -> Result<Website, String> {
+    let monitor = monitors::Monitor::from_input(monitor)?;
+    let mut websites = state.websites.lock().unwrap();
+    let existing: Vec<monitors::Monitor> = websites.iter().map(|w| w.monitor.clone()).collect();
+    monitors::ensure_unique(&existing, &monitor.url, None)?;
+
+    let website = Website::new(monitor);
+    websites.push(website.clone());
+    if let Err(e) = save_monitors(&app_handle, &websites) {
+        websites.pop();
+        return Err(e);
+    }
+    state.scheduler.schedule_now(&website.monitor.id);
+    drop(websites);
+    tray::refresh(&app_handle);
+    Ok(website)
+}
+
+#[tauri::command]
+fn update_website(
+    id: String,
+    monitor: monitors::MonitorInput,
+    state: tauri::State<'_, AppState>,
+    app_handle: AppHandle,
+) -> Result<Website, String> {
+    let mut websites = state.websites.lock().unwrap();
+    let existing: Vec<monitors::Monitor> = websites.iter().map(|w| w.monitor.clone()).collect();
+    let index = websites
+        .iter()
+        .position(|w| w.monitor.id == id)
+        .ok_or_else(|| format!("no website with id {}", id))?;
+
+    let mut updated = websites[index].monitor.clone();
+    updated.apply(monitor)?;
+    monitors::ensure_unique(&existing, &updated.url, Some(&id))?;
+
+    let previous = std::mem::replace(&mut websites[index].monitor, updated);
+    if let Err(e) = save_monitors(&app_handle, &websites) {
+        websites[index].monitor = previous;
+        return Err(e);
+    }
+    state.scheduler.schedule_now(&id);
+    let website = websites[index].clone();
+    drop(websites);
+    if website.monitor.enabled != previous.enabled {
+        record_pause_change(&state, &website.monitor, Utc::now());
+    }
+    tray::refresh(&app_handle);
+    Ok(website)
+}
+
+#[tauri::command]
+fn remove_website(
+    id: String,
+    state: tauri::State<'_, AppState>,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    let mut websites = state.websites.lock().unwrap();
+    let index = websites
+        .iter()
+        .position(|w| w.monitor.id == id)
+        .ok_or_else(|| format!("no website with id {}", id))?;
+
+    let removed = websites.remove(index);
+    if let Err(e) = save_monitors(&app_handle, &websites) {
+        websites.insert(index, removed);
+        return Err(e);
+    }
+    state.scheduler.remove(&id);
+    drop(websites);
+    tray::refresh(&app_handle);
+    // a removed monitor can't recover, so take it out of its incident
+    if let (Some(db), Some(incident_id)) = (&state.db, removed.incident_id) {
+        let resolved_at = Utc::now();
+        match incidents::monitor_recovered(db, incident_id, &id, resolved_at) {
+            Ok(true) => events::emit(
+                &app_handle,
+                events::INCIDENT_RESOLVED,
+                &events::IncidentResolved {
+                    incident_id,
+                    monitor_id: id.clone(),
+                    resolved_at,
+                },
+            ),
+            Ok(false) => {}
+            Err(e) => eprintln!("Failed to update incident {}: {}", incident_id, e),
+        }
+    }
+    Ok(())
+}
+
+#[tauri::command]
+fn pause_monitoring(until: Option<String>, app_handle: AppHandle) -> Result<pauses::GlobalPause, String> {
+    let until = parse_pause_until(until)?;
+    Ok(pause_monitoring_until(&app_handle, until))
+}
+
+#[tauri::command]
+fn resume_monitoring(app_handle: AppHandle) {
+    resume_all_monitoring(&app_handle);
+}
+
+#[tauri::command]
+fn get_network_status(state: tauri::State<'_, AppState>) -> network::NetworkStatus {
+    state.network.status()
+}
+
+// None while monitoring is running
+#[tauri::command]
+fn get_monitoring_status(state: tauri::State<'_, AppState>) -> Option<pauses::GlobalPause> {
+    *state.paused.lock().unwrap()
+}
+
+#[tauri::command]
+fn pause_monitor(id: String, until: Option<String>, app_handle: AppHandle) -> Result<Website, String> {
+    let until = parse_pause_until(until)?;
+    set_website_enabled(&app_handle, &id, false, until)
+}
+
+#[tauri::command]
+fn resume_monitor(id: String, app_handle: AppHandle) -> Result<Website, String> {
+    set_website_enabled(&app_handle, &id, true, None)
+}
+
+// Paused periods overlapping [from, to); for one monitor this includes app-wide pauses
+#[tauri::command]
+fn list_paused_periods(
+    monitor_id: Option<String>,
+    from: Option<String>,
+    to: Option<String>,
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<pauses::Pause>, String> {
+    let db = state.db.as_ref().ok_or("pause history is unavailable")?;
+    pauses::list(db, monitor_id.as_deref(), parse_time(from)?, parse_time(to)?)
+}
+
+#[tauri::command]
+fn acknowledge_monitor(id: String, app_handle: AppHandle) -> Result<Website, String> {
+    acknowledge_website(&app_handle, &id)
+}
+
+#[tauri::command]
+fn snooze_monitor(id: String, duration: String, app_handle: AppHandle) -> Result<Website, String> {
+    // zero cancels the snooze
+    let duration = durations::parse(&duration, mutes::MAX_SNOOZE_DAYS, "a snooze")?;
+    snooze_website(&app_handle, &id, duration)
+}
+
+// Mutes a down monitor until it recovers, and acknowledges its incident as the current user
+fn acknowledge_website(app_handle: &AppHandle, id: &str) -> Result<Website, String> {
+    let state = app_handle.state::<AppState>();
+    let mut websites = state.websites.lock().unwrap();
+    let website = websites
+        .iter_mut()
+        .find(|w| w.monitor.id == id)
+        .ok_or_else(|| format!("no website with id {}", id))?;
+    if website.state != health::HealthState::Down {
+        return Err(format!("{} is not down", website.monitor.url));
+    }
+    website.notification_cleared = true;
+    save_mute_state(&state, website);
+    let website = website.clone();
+    drop(websites);
+    tray::refresh(app_handle);
+
+    if let (Some(db), Some(incident_id)) = (&state.db, website.incident_id) {
+        if let Err(e) = incidents::acknowledge(db, incident_id, &auth::current_user(app_handle), None) {
+            eprintln!("Failed to acknowledge incident {}: {}", incident_id, e);
+        }
+    }
+    Ok(website)
+}
+
+// A zero duration cancels the snooze
+fn snooze_website(app_handle: &AppHandle, id: &str, duration: chrono::Duration) -> Result<Website, String> {
+    let state = app_handle.state::<AppState>();
+    let mut websites = state.websites.lock().unwrap();
+    let website = websites
+        .iter_mut()
+        .find(|w| w.monitor.id == id)
+        .ok_or_else(|| format!("no website with id {}", id))?;
+    website.snoozed_until = (duration > chrono::Duration::zero()).then(|| Utc::now() + duration);
+    save_mute_state(&state, website);
+    let website = website.clone();
+    drop(websites);
+    tray::refresh(app_handle);
+    Ok(website)
+}
+
+// Pausing stops a monitor's checks, optionally until a given time; resuming checks it straight away
+fn set_website_enabled(
+    app_handle: &AppHandle,
+    id: &str,
+    enabled: bool,
+    until: Option<DateTime<Utc>>,
+) -> Result<Website, String> {
+    let state = app_handle.state::<AppState>();
+    let mut websites = state.websites.lock().unwrap();
+    let index = websites
+        .iter()
+        .position(|w| w.monitor.id == id)
+        .ok_or_else(|| format!("no website with id {}", id))?;
+    let previous = websites[index].monitor.clone();
+    websites[index].monitor.enabled = enabled;
+    websites[index].monitor.paused_until = if enabled { None } else { until };
+    if let Err(e) = save_monitors(app_handle, &websites) {
+        websites[index].monitor = previous;
+        return Err(e);
+    }
+    let website = websites[index].clone();
+    drop(websites);
+    // pausing again starts a new period with the new end time
+    if !enabled || !previous.enabled {
+        record_pause_change(&state, &website.monitor, Utc::now());
+    }
+    if enabled {
+        state.scheduler.schedule_now(id);
+    }
+    tray::refresh(app_handle);
+    Ok(website)
+}
+
+// Logs the start or end of a monitor's pause. Pausing also stops uptime crediting time to the
+// state the monitor was last seen in.
+fn record_pause_change(state: &AppState, monitor: &monitors::Monitor, at: DateTime<Utc>) {
+    let Some(db) = &state.db else {
+        return;
+    };
+    let result = if monitor.enabled {
+        pauses::end(db, Some(&monitor.id), at)
+    } else {
+        pauses::start(db, Some(&monitor.id), at, monitor.paused_until)
+            .and_then(|_| uptime::record_check(db, &monitor.id, at, "paused", None, uptime_max_gap_secs(monitor)))
+    };
+    if let Err(e) = result {
+        eprintln!("Failed to record pause of {}: {}", monitor.url, e);
+    }
+}
+
+// Stops all checks (including manual ones) until resumed or until `until`
+fn pause_monitoring_until(app_handle: &AppHandle, until: Option<DateTime<Utc>>) -> pauses::GlobalPause {
+    let state = app_handle.state::<AppState>();
+    let now = Utc::now();
+    let monitors: Vec<monitors::Monitor> = state.websites.lock().unwrap().iter().map(|w| w.monitor.clone()).collect();
+    let pause = {
+        let mut paused = state.paused.lock().unwrap();
+        let pause = pauses::GlobalPause {
+            since: paused.map(|p| p.since).unwrap_or(now),
+            until,
+        };
+        *paused = Some(pause);
+        pause
+    };
+    if let Some(db) = &state.db {
+        if let Err(e) = pauses::start(db, None, now, until) {
+            eprintln!("Failed to record pause: {}", e);
+        }
+        for monitor in monitors.iter().filter(|m| m.enabled) {
+            if let Err(e) = uptime::record_check(db, &monitor.id, now, "paused", None, uptime_max_gap_secs(monitor)) {
+                eprintln!("Failed to update uptime for {}: {}", monitor.url, e);
+            }
+        }
+    }
+    tray::refresh(app_handle);
+    pause
+}
+
+fn resume_all_monitoring(app_handle: &AppHandle) {
+    let state = app_handle.state::<AppState>();
+    if state.paused.lock().unwrap().take().is_none() {
+        return;
+    }
+    if let Some(db) = &state.db {
+        if let Err(e) = pauses::end(db, None, Utc::now()) {
+            eprintln!("Failed to record end of pause: {}", e);
+        }
+    }
+    // the scheduler catches up on everything that fell due during the pause
+    state.scheduler.wake();
+    tray::refresh(app_handle);
+}
+
+// Called by the scheduler; pauses with an end time resume by themselves
+fn resume_expired_pauses(app_handle: &AppHandle) {
+    let state = app_handle.state::<AppState>();
+    let now = Utc::now();
+    let global_expired = state
+        .paused
+        .lock()
+        .unwrap()
+        .is_some_and(|p| p.until.is_some_and(|until| until <= now));
+    if global_expired {
+        resume_all_monitoring(app_handle);
+    }
+    let expired: Vec<String> = state
+        .websites
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|w| !w.monitor.enabled && w.monitor.paused_until.is_some_and(|until| until <= now))
+        .map(|w| w.monitor.id.clone())
+        .collect();
+    for id in expired {
+        if let Err(e) = set_website_enabled(app_handle, &id, true, None) {
+            eprintln!("Failed to resume {}: {}", id, e);
+        }
+    }
+}
+
+fn is_paused(state: &AppState, monitor: &monitors::Monitor) -> bool {
+    !monitor.enabled || state.paused.lock().unwrap().is_some()
+}
+
+// A pause end time given as RFC 3339 or as a duration from now ("90m", "2h")
+fn parse_pause_until(until: Option<String>) -> Result<Option<DateTime<Utc>>, String> {
+    let Some(until) = until.map(|u| u.trim().to_string()).filter(|u| !u.is_empty()) else {
+        return Ok(None);
+    };
+    let now = Utc::now();
+    let at = match DateTime::parse_from_rfc3339(&until) {
+        Ok(at) => at.with_timezone(&Utc),
+        Err(_) => now + durations::parse(&until, pauses::MAX_PAUSE_DAYS, "a pause")?,
+    };
+    if at <= now {
+        return Err("pause end must be in the future".to_string());
+    }
+    if at > now + chrono::Duration::days(pauses::MAX_PAUSE_DAYS) {
+        return Err(format!("a pause can last at most {} days", pauses::MAX_PAUSE_DAYS));
+    }
+    Ok(Some(at))
+}
+
+// cap on how much time since the previous check uptime will credit, so gaps where the
+// app wasn't running don't count
+fn uptime_max_gap_secs(monitor: &monitors::Monitor) -> u64 {
+    (monitor.schedule.nominal_interval_secs() * 2).max(120)
+}
+
+fn save_mute_state(state: &AppState, website: &Website) {
+    if let Some(db) = &state.db {
+        if let Err(e) = mutes::save(db, &website.monitor.id, &website.mute_state()) {
+            eprintln!("Failed to save notification state for {}: {}", website.monitor.url, e);
+        }
+    }
+}
+
+// Tray actions apply to every monitor that is currently failing
+fn acknowledge_all_down(app_handle: &AppHandle) {
+    let ids: Vec<String> = app_handle
+        .state::<AppState>()
+        .websites
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|w| w.state == health::HealthState::Down && !w.notification_cleared)
+        .map(|w| w.monitor.id.clone())
+        .collect();
+    for id in ids {
+        if let Err(e) = acknowledge_website(app_handle, &id) {
+            eprintln!("Failed to acknowledge {}: {}", id, e);
+        }
+    }
+}
+
+fn snooze_all_failing(app_handle: &AppHandle, duration: chrono::Duration) {
+    let ids: Vec<String> = app_handle
+        .state::<AppState>()
+        .websites
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|w| w.state != health::HealthState::Up)
+        .map(|w| w.monitor.id.clone())
+        .collect();
+    for id in ids {
+        if let Err(e) = snooze_website(app_handle, &id, duration) {
+            eprintln!("Failed to snooze {}: {}", id, e);
+        }
+    }
+}
+
+#[tauri::command]
+fn get_settings(state: tauri::State<'_, AppState>) -> settings::AppSettings {
+    state.settings.lock().unwrap().clone()
+}
+
+#[tauri::command]
+fn update_settings(
+    settings: settings::AppSettings,
+    state: tauri::State<'_, AppState>,
+    app_handle: AppHandle,
+) -> Result<settings::AppSettings, String> {
+    settings.validate()?;
+    settings::save(&app_handle, &settings)?;
+    let mut engine = state.engine.lock().unwrap();
+    *engine = Arc::new(engine.with_limits(&settings));
+    *state.settings.lock().unwrap() = settings.clone();
+    Ok(settings)
+}
+
+#[tauri::command]
+fn list_maintenance_windows(state: tauri::State<'_, AppState>) -> Vec<maintenance::MaintenanceWindow> {
+    state.maintenance.lock().unwrap().clone()
+}
+
+#[tauri::command]
+fn add_maintenance_window(
+    window: maintenance::MaintenanceWindowInput,
+    state: tauri::State<'_, AppState>,
+    app_handle: AppHandle,
+) -> Result<maintenance::MaintenanceWindow, String> {
+    let window = maintenance::MaintenanceWindow::from_input(window)?;
+    let mut windows = state.maintenance.lock().unwrap();
+    windows.push(window.clone());
+    if let Err(e) = maintenance::save(&app_handle, &windows) {
+        windows.pop();
+        return Err(e);
+    }
+    Ok(window)
+}
+
+#[tauri::command]
+fn update_maintenance_window(
+    id: String,
+    window: maintenance::MaintenanceWindowInput,
+    state: tauri::State<'_, AppState>,
+    app_handle: AppHandle,
+) -> Result<maintenance::MaintenanceWindow, String> {
+    let mut windows = state.maintenance.lock().unwrap();
+    let index = windows
+        .iter()
+        .position(|w| w.id == id)
+        .ok_or_else(|| format!("no maintenance window with id {}", id))?;
+    let mut updated = windows[index].clone();
+    updated.apply(window)?;
+    let previous = std::mem::replace(&mut windows[index], updated.clone());
+    if let Err(e) = maintenance::save(&app_handle, &windows) {
+        windows[index] = previous;
+        return Err(e);
+    }
+    Ok(updated)
+}
+
+#[tauri::command]
+fn remove_maintenance_window(
+    id: String,
+    state: tauri::State<'_, AppState>,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    let mut windows = state.maintenance.lock().unwrap();
+    let index = windows
+        .iter()
+        .position(|w| w.id == id)
+        .ok_or_else(|| format!("no maintenance window with id {}", id))?;
+    let removed = windows.remove(index);
+    if let Err(e) = maintenance::save(&app_handle, &windows) {
+        windows.insert(index, removed);
+        return Err(e);
+    }
+    Ok(())
+}
+
+// `contents` is the text of an .ics file; every usable event becomes a window for the given
+// targets, and the rest come back in `skipped` with the reason
+#[tauri::command]
+fn import_maintenance_ics(
+    contents: String,
+    monitor_ids: Vec<String>,
+    groups: Vec<String>,
+    state: tauri::State<'_, AppState>,
+    app_handle: AppHandle,
+) -> Result<maintenance::IcsImport, String> {
+    let imported = maintenance::import_ics(&contents, monitor_ids, groups)?;
+    for skipped in &imported.skipped {
+        eprintln!("Skipped calendar event '{}': {}", skipped.event, skipped.error);
+    }
+    let mut windows = state.maintenance.lock().unwrap();
+    let count = windows.len();
+    windows.extend(imported.windows.iter().cloned());
+    if let Err(e) = maintenance::save(&app_handle, &windows) {
+        windows.truncate(count);
+        return Err(e);
+    }
+    Ok(imported)
+}
+
+#[tauri::command]
+fn list_channels(state: tauri::State<'_, AppState>) -> Vec<notifiers::Channel> {
+    state.channels.lock().unwrap().clone()
+}
+
+#[tauri::command]
+fn add_channel(
+    channel: notifiers::ChannelInput,
+    state: tauri::State<'_, AppState>,
+    app_handle: AppHandle,
+) -> Result<notifiers::Channel, String> {
+    if matches!(channel.kind, notifiers::ChannelKind::Desktop(_)) {
+        return Err("there is already a desktop channel".to_string());
+    }
+    let secret = channel.secret.clone();
+    let mut channel = notifiers::Channel::from_input(channel)?;
+    if let Some(secret) = secret.filter(|s| !s.is_empty()) {
+        notifiers::store_secret(&app_handle, &channel.id, Some(&secret))?;
+        channel.has_secret = true;
+    }
+    let mut channels = state.channels.lock().unwrap();
+    channels.push(channel.clone());
+    if let Err(e) = notifiers::save(&app_handle, &channels) {
+        channels.pop();
+        let _ = notifiers::store_secret(&app_handle, &channel.id, None);
+        return Err(e);
+    }
+    Ok(channel)
+}
+
+#[tauri::command]
+fn update_channel(
+    id: String,
+    channel: notifiers::ChannelInput,
+    state: tauri::State<'_, AppState>,
+    app_handle: AppHandle,
+) -> Result<notifiers::Channel, String> {
+    let mut channels = state.channels.lock().unwrap();
+    let index = channels
+        .iter()
+        .position(|c| c.id == id)
+        .ok_or_else(|| format!("no channel with id {}", id))?;
+    let mut updated = channels[index].clone();
+    let is_desktop = |kind: &notifiers::ChannelKind| matches!(kind, notifiers::ChannelKind::Desktop(_));
+    if is_desktop(&updated.kind) != is_desktop(&channel.kind) {
+        return Err("the desktop channel can't change type".to_string());
+    }
+    let secret = channel.secret.clone();
+    updated.apply(channel)?;
+    if let Some(secret) = secret {
+        let secret = Some(secret.as_str()).filter(|s| !s.is_empty());
+        notifiers::store_secret(&app_handle, &id, secret)?;
+        updated.has_secret = secret.is_some();
+    }
+    let previous = std::mem::replace(&mut channels[index], updated.clone());
+    if let Err(e) = notifiers::save(&app_handle, &channels) {
+        channels[index] = previous;
+        return Err(e);
+    }
+    Ok(updated)
+}
+
+#[tauri::command]
+fn remove_channel(id: String, state: tauri::State<'_, AppState>, app_handle: AppHandle) -> Result<(), String> {
+    // turn it off instead; rules may name it
+    if id == notifiers::desktop::CHANNEL_ID {
+        return Err("the desktop channel can't be removed, only disabled".to_string());
+    }
+    let mut channels = state.channels.lock().unwrap();
+    let index = channels
+        .iter()
+        .position(|c| c.id == id)
+        .ok_or_else(|| format!("no channel with id {}", id))?;
+    let removed = channels.remove(index);
+    if let Err(e) = notifiers::save(&app_handle, &channels) {
+        channels.insert(index, removed);
+        return Err(e);
+    }
+    if let Err(e) = notifiers::store_secret(&app_handle, &id, None) {
+        eprintln!("Failed to remove secret for channel {}: {}", removed.name, e);
+    }
+    Ok(())
+}
+
+// Sends a test alert right away (with the usual retries) and returns how it went
+#[tauri::command]
+async fn test_channel(
+    id: String,
+    state: tauri::State<'_, AppState>,
+    app_handle: AppHandle,
+) -> Result<notifiers::deliveries::Delivery, String> {
+    let channel = state
+        .channels
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|c| c.id == id)
+        .cloned()
+        .ok_or_else(|| format!("no channel with id {}", id))?;
+    Ok(notifiers::deliver(&app_handle, &channel, &notifiers::AlertEvent::test()).await)
+}
+
+#[tauri::command]
+fn list_routing_rules(state: tauri::State<'_, AppState>) -> Vec<routing::RoutingRule> {
+    state.routing_rules.lock().unwrap().clone()
+}
+
+#[tauri::command]
+fn add_routing_rule(
+    rule: routing::RoutingRuleInput,
+    state: tauri::State<'_, AppState>,
+    app_handle: AppHandle,
+) -> Result<routing::RoutingRule, String> {
+    let rule = routing::RoutingRule::from_input(rule)?;
+    let mut rules = state.routing_rules.lock().unwrap();
+    rules.push(rule.clone());
+    if let Err(e) = routing::save(&app_handle, &rules) {
+        rules.pop();
+        return Err(e);
+    }
+    Ok(rule)
+}
+
+#[tauri::command]
+fn update_routing_rule(
+    id: String,
+    rule: routing::RoutingRuleInput,
+    state: tauri::State<'_, AppState>,
+    app_handle: AppHandle,
+) -> Result<routing::RoutingRule, String> {
+    let mut rules = state.routing_rules.lock().unwrap();
+    let index = rules
+        .iter()
+        .position(|r| r.id == id)
+        .ok_or_else(|| format!("no routing rule with id {}", id))?;
+    let mut updated = rules[index].clone();
+    updated.apply(rule)?;
+    let previous = std::mem::replace(&mut rules[index], updated.clone());
+    if let Err(e) = routing::save(&app_handle, &rules) {
+        rules[index] = previous;
+        return Err(e);
+    }
+    Ok(updated)
+}
+
+// Rules are evaluated in list order, so moving one changes which rule wins
+#[tauri::command]
+fn move_routing_rule(
+    id: String,
+    index: usize,
+    state: tauri::State<'_, AppState>,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    let mut rules = state.routing_rules.lock().unwrap();
+    let from = rules
+        .iter()
+        .position(|r| r.id == id)
+        .ok_or_else(|| format!("no routing rule with id {}", id))?;
+    let to = index.min(rules.len() - 1);
+    let rule = rules.remove(from);
+    rules.insert(to, rule);
+    if let Err(e) = routing::save(&app_handle, &rules) {
+        let rule = rules.remove(to);
+        rules.insert(from, rule);
+        return Err(e);
+    }
+    Ok(())
+}
+
+#[tauri::command]
+fn remove_routing_rule(id: String, state: tauri::State<'_, AppState>, app_handle: AppHandle) -> Result<(), String> {
+    let mut rules = state.routing_rules.lock().unwrap();
+    let index = rules
+        .iter()
+        .position(|r| r.id == id)
+        .ok_or_else(|| format!("no routing rule with id {}", id))?;
+    let removed = rules.remove(index);
+    if let Err(e) = routing::save(&app_handle, &rules) {
+        rules.insert(index, removed);
+        return Err(e);
+    }
+    Ok(())
+}
+
+// Dry run: which channels would hear about this monitor changing to `new_state` at `at`
+// (default now), without sending anything
+#[tauri::command]
+fn preview_routing(
+    monitor_id: String,
+    new_state: String,
+    at: Option<String>,
+    state: tauri::State<'_, AppState>,
+) -> Result<routing::RoutingPlan, String> {
+    let now = parse_time(at)?.unwrap_or_else(Utc::now);
+    let new_state = new_state.trim().to_lowercase();
+    let (event, routes, notify_recovery) = {
+        let websites = state.websites.lock().unwrap();
+        let website = websites
+            .iter()
+            .find(|w| w.monitor.id == monitor_id)
+            .ok_or_else(|| format!("no monitor with id {}", monitor_id))?;
+        // going down opens an incident, which is what escalation steps wait on
+        let incident_id = website.incident_id.or((new_state == "down").then_some(0));
+        let event = notifiers::AlertEvent::state_changed(
+            &website.monitor,
+            website.state.label(),
+            &new_state,
+            None,
+            None,
+            incident_id,
+            website.down_since,
+        );
+        let notify_recovery = website
+            .monitor
+            .notify_recovery
+            .unwrap_or_else(|| state.settings.lock().unwrap().notify_recovery);
+        (event, website.monitor.channels.clone(), notify_recovery)
+    };
+    let rules = state.routing_rules.lock().unwrap();
+    let channels = state.channels.lock().unwrap();
+    Ok(routing::plan(&rules, &channels, &event, routes.as_deref(), notify_recovery, now))
+}
+
+#[tauri::command]
+fn get_delivery_log(
+    channel_id: Option<String>,
+    limit: Option<u32>,
+    offset: Option<u32>,
+    state: tauri::State<'_, AppState>,
+) -> Result<notifiers::deliveries::DeliveryPage, String> {
+    let db = state.db.as_ref().ok_or("delivery log is unavailable")?;
+    notifiers::deliveries::query(db, channel_id.as_deref(), limit, offset)
+}
+
+#[tauri::command]
+fn get_check_history(
+    monitor_id: String,
+    from: Option<String>,
+    to: Option<String>,
+    limit: Option<u32>,
+    offset: Option<u32>,
+    state: tauri::State<'_, AppState>,
+) -> Result<history::HistoryPage, String> {
+    let db = state.db.as_ref().ok_or("check history is unavailable")?;
+    history::query(db, &monitor_id, parse_time(from)?, parse_time(to)?, limit, offset)
+}
+
+#[tauri::command]
+fn get_uptime_report(
+    window: String,
+    from: Option<String>,
+    to: Option<String>,
+    monitor_id: Option<String>,
+    group: Option<String>,
+    state: tauri::State<'_, AppState>,
+) -> Result<uptime::UptimeReport, String> {
+    let db = state.db.as_ref().ok_or("check history is unavailable")?;
+    let window = uptime::Window::parse(&window, parse_time(from)?, parse_time(to)?)?;
+    let monitors: Vec<uptime::MonitorRef> = state
+        .websites
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|w| monitor_id.as_ref().map(|id| &w.monitor.id == id).unwrap_or(true))
+        .filter(|w| group.is_none() || w.monitor.group == group)
+        .map(|w| uptime::MonitorRef {
+            id: w.monitor.id.clone(),
+            name: w.monitor.name.clone(),
+            group: w.monitor.group.clone(),
+            sla_target: w.monitor.sla_target,
+        })
+        .collect();
+    uptime::report(db, &monitors, window)
+}
+
+#[tauri::command]
+fn list_incidents(
+    status: Option<String>,
+    monitor_id: Option<String>,
+    from: Option<String>,
+    to: Option<String>,
+    limit: Option<u32>,
+    offset: Option<u32>,
+    state: tauri::State<'_, AppState>,
+) -> Result<incidents::IncidentPage, String> {
+    let db = state.db.as_ref().ok_or("incident history is unavailable")?;
+    if let Some(status) = status.as_deref() {
+        if !["open", "acknowledged", "resolved"].contains(&status) {
+            return Err(format!("unknown incident status '{}'", status));
         }
-        Err(e) => Err(e),
+    }
+    incidents::list(
+        db,
+        status.as_deref(),
+        monitor_id.as_deref(),
+        parse_time(from)?,
+        parse_time(to)?,
+        limit,
+        offset,
+    )
+}
+
+#[tauri::command]
+fn get_incident(id: i64, state: tauri::State<'_, AppState>) -> Result<incidents::Incident, String> {
+    let db = state.db.as_ref().ok_or("incident history is unavailable")?;
+    incidents::get(db, id)?.ok_or_else(|| format!("no incident with id {}", id))
+}
+
+#[tauri::command]
+fn acknowledge_incident(
+    id: i64,
+    note: Option<String>,
+    state: tauri::State<'_, AppState>,
+    app_handle: AppHandle,
+) -> Result<incidents::Incident, String> {
+    let db = state.db.as_ref().ok_or("incident history is unavailable")?;
+    let user = auth::current_user(&app_handle);
+    let incident = incidents::acknowledge(db, id, &user, note.as_deref())?;
+    Ok(incident)
+}
+
+#[tauri::command]
+fn add_incident_note(
+    id: i64,
+    body: String,
+    state: tauri::State<'_, AppState>,
+    app_handle: AppHandle,
+) -> Result<incidents::Incident, String> {
+    let db = state.db.as_ref().ok_or("incident history is unavailable")?;
+    incidents::add_note(db, id, &auth::current_user(&app_handle), &body)
+}
+
+fn parse_time(value: Option<String>) -> Result<Option<DateTime<Utc>>, String> {
+    match value.as_deref().map(str::trim).filter(|v| !v.is_empty()) {
+        Some(v) => DateTime::parse_from_rfc3339(v)
+            .map(|t| Some(t.with_timezone(&Utc)))
+            .map_err(|e| format!("invalid time '{}': {}", v, e)),
+        None => Ok(None),
     }
 }
 
@@ -291,58 +1236,565 @@ fn logout(user: String, app_handle: AppHandle) -> Result<(), String> {
 }
 
 async fn do_check_websites(
-    mut websites: Vec<Website>,
+    websites: Vec<Website>,
     app_handle: AppHandle,
-) -> Result<(Vec<Website>, String), String> {
-    
-    for website in websites.iter_mut() {
-        let was_down = website.is_down;
-        let client = reqwest::Client::builder()
-            .timeout(std::time::Duration::from_secs(10))
-            .build()
-            .unwrap();
-
-        println!("Checking site: {}", website.url);
-        
-        match client.get(&website.url).send().await {
-            Ok(response) => {
-                website.is_down = !response.status().is_success();
-                println!("Site is up: {}", website.url);
+) -> Result<String, String> {
+    let engine = app_handle.state::<AppState>().engine.lock().unwrap().clone();
+    let started_at = Utc::now();
+    let mut cycle = CheckCycle {
+        // results from checks that were running across a suspend or clock change are stale
+        generation: app_handle.state::<AppState>().clock.generation(),
+        app_handle: app_handle.clone(),
+        engine: Arc::clone(&engine),
+        rechecks: JoinSet::new(),
+        checked: 0,
+        dropped: 0,
+    };
+
+    // without canaries, a failure to connect waits for the rest of the pass: if every site (on
+    // at least two hosts) failed that way, it is our own connection that is down
+    let hosts: std::collections::HashSet<Option<String>> = websites
+        .iter()
+        .map(|w| url::Url::parse(&w.monitor.url).ok()?.host_str().map(String::from))
+        .collect();
+    let hold_network_errors =
+        hosts.len() >= 2 && app_handle.state::<AppState>().settings.lock().unwrap().canary_targets.is_empty();
+    let mut held = Vec::new();
+    let mut judged = 0;
+
+    // run every check concurrently (bounded by the engine) and handle each result as it lands
+    let mut checks = JoinSet::new();
+    for website in websites {
+        let engine = Arc::clone(&engine);
+        checks.spawn(async move {
+            let result = engine.check(&website.monitor).await;
+            (website, result)
+        });
+    }
+    while let Some(joined) = checks.join_next().await {
+        let (website, result) = match joined {
+            Ok(done) => done,
+            Err(e) => {
+                eprintln!("Website check task failed: {}", e);
+                continue;
             }
-            Err(_) => {
-                website.is_down = true;
-                println!("Site is down: {}", website.url);
+        };
+        if !cycle.stale() {
+            judged += 1;
+            if hold_network_errors && network_error(&result) {
+                held.push((website, result));
+                continue;
             }
         }
-        
-        website.last_checked = chrono::Utc::now().to_rfc3339();
-        
-        // Send notification if website just went down and hasn't been cleared
-        if website.is_down && !was_down && !website.notification_cleared {
-            let _ = app_handle
-                .notification()
-                .builder()
-                .title("Website Down!")
-                .body(format!("{} is not responding", website.url))
-                .show();
-        }
-        
-        // Reset notification flag if website is back up
-        if !website.is_down && was_down {
+        cycle.apply(website, result).await;
+    }
+    if !held.is_empty() && held.len() == judged {
+        let state = app_handle.state::<AppState>();
+        if state.network.set_offline(true, Utc::now()) {
+            network_changed(&app_handle, false);
+        }
+        cycle.dropped += held.len() as u32;
+    } else {
+        for (website, result) in held {
+            cycle.apply(website, result).await;
+        }
+    }
+    while let Some(joined) = cycle.rechecks.join_next().await {
+        match joined {
+            Ok((website, result)) => cycle.apply(website, result).await,
+            Err(e) => eprintln!("Website check task failed: {}", e),
+        }
+    }
+    let CheckCycle { checked, dropped, .. } = cycle;
+
+    let completed_at = Utc::now();
+    let message = format!("Websites checked successfully at {}!", completed_at.format("%Y-%m-%d %H:%M:%S"));
+
+    let mut summary = events::CheckCycleCompleted {
+        started_at,
+        completed_at,
+        checked,
+        dropped,
+        up: 0,
+        degraded: 0,
+        down: 0,
+        maintenance: 0,
+        paused: 0,
+    };
+    {
+        let state = app_handle.state::<AppState>();
+        let websites = state.websites.lock().unwrap();
+        for website in websites.iter() {
+            let count = if is_paused(&state, &website.monitor) {
+                &mut summary.paused
+            } else {
+                match website.state {
+                    health::HealthState::Up => &mut summary.up,
+                    health::HealthState::Degraded => &mut summary.degraded,
+                    health::HealthState::Down => &mut summary.down,
+                    health::HealthState::Maintenance => &mut summary.maintenance,
+                }
+            };
+            *count += 1;
+        }
+    }
+    events::emit(&app_handle, events::CHECK_CYCLE_COMPLETED, &summary);
+    events::emit(
+        &app_handle,
+        events::WEBSITE_CHECK_COMPLETE,
+        &events::WebsiteCheckComplete { message: message.clone() },
+    );
+    tray::refresh(&app_handle);
+
+   Ok(message)
+}
+
+// The checks of one do_check_websites call
+struct CheckCycle {
+    app_handle: AppHandle,
+    engine: Arc<checker::CheckEngine>,
+    generation: u64,
+    // quick re-checks of unconfirmed failures, run as part of the same cycle
+    rechecks: JoinSet<(Website, checker::CheckResult)>,
+    checked: u32,
+    dropped: u32,
+}
+
+impl CheckCycle {
+    fn stale(&self) -> bool {
+        self.app_handle.state::<AppState>().clock.generation() != self.generation
+    }
+
+    async fn apply(&mut self, website: Website, result: checker::CheckResult) {
+        if self.stale() || network_unreachable(&self.app_handle, &result).await {
+            self.dropped += 1;
+            return;
+        }
+        self.checked += 1;
+        if let Some(delay) = record_check_result(&self.app_handle, &website.monitor.id, result) {
+            let engine = Arc::clone(&self.engine);
+            self.rechecks.spawn(async move {
+                tokio::time::sleep(delay).await;
+                let result = engine.check(&website.monitor).await;
+                (website, result)
+            });
+        }
+    }
+}
+
+// A failure that could be our own connection's fault
+fn network_error(result: &checker::CheckResult) -> bool {
+    result.error.as_ref().is_some_and(|e| {
+        matches!(
+            e.class,
+            checker::ErrorClass::Dns | checker::ErrorClass::Timeout | checker::ErrorClass::Connect
+        )
+    })
+}
+
+// Network failures are only judged once a canary answers. While none do, the app is offline:
+// results are dropped, so no site changes state or alerts. Without canaries it goes offline
+// when a whole pass fails to connect (see do_check_websites) and stays so until a site answers.
+async fn network_unreachable(app_handle: &AppHandle, result: &checker::CheckResult) -> bool {
+    let state = app_handle.state::<AppState>();
+    let online = if network_error(result) {
+        let targets = state.settings.lock().unwrap().canary_targets.clone();
+        if targets.is_empty() {
+            state.network.status().online
+        } else {
+            state.network.probe(&targets).await
+        }
+    } else {
+        state.network.saw_response();
+        true
+    };
+    if state.network.set_offline(!online, Utc::now()) {
+        network_changed(app_handle, online);
+    }
+    !online
+}
+
+// Called by the scheduler after a suspend or clock change, before it runs a catch-up check
+fn clock_jumped(app_handle: &AppHandle, jump: clock::Jump) {
+    let state = app_handle.state::<AppState>();
+    state.network.forget();
+    if let Some(db) = &state.db {
+        routing::clock_jumped(db, jump);
+    }
+    // the gap is neither uptime nor downtime
+    if let (clock::Jump::Gap { since }, Some(db)) = (jump, &state.db) {
+        let monitors: Vec<monitors::Monitor> =
+            state.websites.lock().unwrap().iter().map(|w| w.monitor.clone()).collect();
+        for monitor in monitors.iter().filter(|m| m.enabled) {
+            if let Err(e) = uptime::record_check(db, &monitor.id, since, "suspended", None, uptime_max_gap_secs(monitor)) {
+                eprintln!("Failed to update uptime for {}: {}", monitor.url, e);
+            }
+        }
+    }
+}
+
+fn network_changed(app_handle: &AppHandle, online: bool) {
+    let state = app_handle.state::<AppState>();
+    if online {
+        show_notification(app_handle, "Back online", "Website checks have resumed");
+        state.scheduler.wake();
+    } else {
+        show_notification(
+            app_handle,
+            "You are offline",
+            "Sites can't be reached from this machine. They won't be marked down until the connection is back.",
+        );
+        // like a pause, the time offline isn't credited to any monitor's uptime
+        if let Some(db) = &state.db {
+            let now = Utc::now();
+            let monitors: Vec<monitors::Monitor> =
+                state.websites.lock().unwrap().iter().map(|w| w.monitor.clone()).collect();
+            for monitor in monitors.iter().filter(|m| m.enabled) {
+                if let Err(e) = uptime::record_check(db, &monitor.id, now, "offline", None, uptime_max_gap_secs(monitor)) {
+                    eprintln!("Failed to update uptime for {}: {}", monitor.url, e);
+                }
+            }
+        }
+    }
+    tray::refresh(app_handle);
+}
+
+// Applies a check result to the monitor's state. Returns a delay when the result was an
+// unconfirmed failure and the monitor wants a quick re-check to confirm it.
+fn record_check_result(app_handle: &AppHandle, id: &str, result: checker::CheckResult) -> Option<Duration> {
+    let state = app_handle.state::<AppState>();
+    let mut websites = state.websites.lock().unwrap();
+    // the site may have been removed while it was being checked
+    let website = websites.iter_mut().find(|w| w.monitor.id == id)?;
+    // paused while the check was running; its result would count towards uptime
+    if is_paused(&state, &website.monitor) {
+        return None;
+    }
+    let previous = website.state;
+    // an outage stays open through a maintenance window; only a real up or degraded result ends it
+    let outage_open = website.down_since.is_some();
+
+    let (observed, reason) = health::evaluate(&result, &website.monitor.thresholds);
+    let now = Utc::now();
+    let maintenance_window = maintenance::active_for(&state.maintenance.lock().unwrap(), &website.monitor, now)
+        .map(|w| w.name.clone());
+    let assessment = match &maintenance_window {
+        // nothing gets confirmed during maintenance; the tracker starts afresh once it ends,
+        // from down if the monitor went into the window down
+        Some(_) => {
+            website.tracker = health::HealthTracker::default();
+            health::Assessment {
+                state: health::HealthState::Maintenance,
+                awaiting_confirmation: false,
+                flapping_started: false,
+                flapping_ended: false,
+            }
+        }
+        None => website.tracker.observe(
+            match previous {
+                health::HealthState::Maintenance if outage_open => health::HealthState::Down,
+                health::HealthState::Maintenance => health::HealthState::Up,
+                _ => previous,
+            },
+            observed,
+            &website.monitor.confirmation,
+            &website.monitor.flap_detection,
+            now,
+        ),
+    };
+    let health = assessment.state;
+    website.state = health;
+    // while a change is unconfirmed the monitor keeps the reason it already had
+    if let Some(name) = &maintenance_window {
+        website.state_reason = Some(format!("in maintenance window '{}'", name));
+    } else if health == observed {
+        website.state_reason = reason;
+    } else if previous == health::HealthState::Maintenance {
+        website.state_reason = if outage_open { website.last_error.clone() } else { reason };
+    }
+
+    website.last_checked = result.checked_at.clone();
+    // certificate alerts wait until the maintenance window is over
+    let mut certificate_alerts = Vec::new();
+    if let Some(certificate) = result.certificate.as_ref().filter(|_| maintenance_window.is_none()) {
+        let warning_days = website
+            .monitor
+            .certificate_warning_days
+            .clone()
+            .unwrap_or_else(|| state.settings.lock().unwrap().certificate_warning_days.clone());
+        let before = website.certificate_alerts.clone();
+        let alerts = tls::evaluate(certificate, &warning_days, &mut website.certificate_alerts);
+        if website.certificate_alerts != before {
+            if let Err(e) = tls::save_alert_state(app_handle, &website.monitor.id, &website.certificate_alerts) {
+                eprintln!("Failed to save certificate state for {}: {}", website.monitor.url, e);
+            }
+        }
+        let host = url::Url::parse(&website.monitor.url)
+            .ok()
+            .and_then(|u| u.host_str().map(|h| h.to_string()))
+            .unwrap_or_else(|| website.monitor.url.clone());
+        for alert in alerts {
+            let description = alert.describe(&host);
+            certificate_alerts.push(notifiers::AlertEvent::certificate_warning(
+                &website.monitor,
+                health.label(),
+                description,
+            ));
+        }
+    }
+    if let Some(certificate) = &result.certificate {
+        website.certificate = Some(certificate.clone());
+    }
+    let history_record = history::CheckRecord {
+        monitor_id: website.monitor.id.clone(),
+        checked_at: DateTime::parse_from_rfc3339(&result.checked_at)
+            .map(|t| t.with_timezone(&Utc))
+            .unwrap_or_else(|_| Utc::now()),
+        state: health.label().to_string(),
+        observed_state: observed.label().to_string(),
+        status_code: result.status_code,
+        latency_ms: result.response_time_ms,
+        response_size: result.response_size,
+        error_class: result.error.as_ref().map(|e| e.class.code()),
+        error_message: result.error.as_ref().map(|e| e.message.clone()),
+    };
+    let max_gap_secs = uptime_max_gap_secs(&website.monitor);
+    website.last_result = Some(result);
+
+    if assessment.flapping_started {
+        show_notification(
+            app_handle,
+            "Website Flapping",
+            &format!(
+                "{} keeps changing state; further alerts are held until it settles",
+                website.monitor.url
+            ),
+        );
+    } else if assessment.flapping_ended {
+        show_notification(
+            app_handle,
+            "Website Settled",
+            &format!("{} has stopped flapping and is {}", website.monitor.url, health.label()),
+        );
+    }
+    if website.snoozed_until.is_some_and(|until| until <= now) {
+        website.snoozed_until = None;
+        save_mute_state(&state, website);
+    }
+    let snoozed = website.snoozed_until.is_some();
+    let muted = website.mute_state().is_muted(now) || website.tracker.flapping || maintenance_window.is_some();
+
+    let went_down =
+        health == health::HealthState::Down && previous != health::HealthState::Down && !outage_open;
+
+    let error_changed = observed == health::HealthState::Down && website.last_error != website.state_reason;
+    if observed == health::HealthState::Down {
+        website.last_error = website.state_reason.clone();
+    }
+    let mut incident_opened = None;
+    let mut incident_resolved = None;
+    let mut website_recovered = None;
+    if went_down {
+        let down_since = Utc::now();
+        website.down_since = Some(down_since);
+        if let Some(db) = &state.db {
+            match incidents::open_or_join(
+                db,
+                &website.monitor.id,
+                website.monitor.group.as_deref(),
+                website.last_error.as_deref(),
+                down_since,
+            ) {
+                Ok((incident_id, opened)) => {
+                    website.incident_id = Some(incident_id);
+                    incident_opened = opened.then(|| events::IncidentOpened {
+                        incident_id,
+                        monitor_id: website.monitor.id.clone(),
+                        group: website.monitor.group.clone(),
+                        error: website.last_error.clone(),
+                        started_at: down_since,
+                    });
+                }
+                Err(e) => eprintln!("Failed to open incident for {}: {}", website.monitor.url, e),
+            }
+        }
+    } else if health == health::HealthState::Down && error_changed {
+        if let (Some(db), Some(incident_id), Some(error)) =
+            (&state.db, website.incident_id, website.last_error.as_deref())
+        {
+            if let Err(e) = incidents::record_error(db, incident_id, error) {
+                eprintln!("Failed to update incident {}: {}", incident_id, e);
+            }
+        }
+    }
+
+    // Alert channels, the desktop included, hear about every confirmed state change unless the
+    // monitor is muted; coming out of maintenance unchanged isn't news
+    let recovered =
+        matches!(health, health::HealthState::Up | health::HealthState::Degraded) && outage_open;
+    let unchanged_by_maintenance = previous == health::HealthState::Maintenance
+        && match health {
+            health::HealthState::Up => !recovered,
+            health::HealthState::Down => outage_open,
+            _ => false,
+        };
+    let alert = (health != previous && !unchanged_by_maintenance)
+        .then(|| {
+            let error = match health {
+                health::HealthState::Degraded => website.state_reason.clone(),
+                _ => website.last_error.clone(),
+            };
+            notifiers::AlertEvent::state_changed(
+                &website.monitor,
+                previous.label(),
+                health.label(),
+                error,
+                history_record.status_code.map(|_| history_record.latency_ms),
+                website.incident_id,
+                website.down_since,
+            )
+        })
+        .filter(|_| {
+            if recovered {
+                !website.tracker.flapping && !snoozed && maintenance_window.is_none()
+            } else {
+                !muted
+            }
+        });
+
+    let alert_routes = website.monitor.channels.clone();
+    let notify_recovery = website
+        .monitor
+        .notify_recovery
+        .unwrap_or_else(|| state.settings.lock().unwrap().notify_recovery);
+    let state_changed = (health != previous).then(|| events::MonitorStateChanged {
+        monitor_id: website.monitor.id.clone(),
+        name: website.monitor.name.clone(),
+        url: website.monitor.url.clone(),
+        group: website.monitor.group.clone(),
+        old_state: previous.label().to_string(),
+        new_state: health.label().to_string(),
+        reason: website.state_reason.clone(),
+        incident_id: website.incident_id,
+        changed_at: now,
+    });
+
+    // Reset notification flag if website is back up
+    if recovered {
+        if website.notification_cleared {
             website.notification_cleared = false;
+            save_mute_state(&state, website);
+        }
+
+        let recovered_at = Utc::now();
+        let down_since = website.down_since.take().unwrap_or(recovered_at);
+        let last_error = website.last_error.take();
+        if let (Some(db), Some(incident_id)) = (&state.db, website.incident_id.take()) {
+            match incidents::monitor_recovered(db, incident_id, &website.monitor.id, recovered_at) {
+                Ok(true) => {
+                    incident_resolved = Some(events::IncidentResolved {
+                        incident_id,
+                        monitor_id: website.monitor.id.clone(),
+                        resolved_at: recovered_at,
+                    });
+                }
+                Ok(false) => {}
+                Err(e) => eprintln!("Failed to update incident {}: {}", incident_id, e),
+            }
         }
+        website_recovered = Some(events::WebsiteRecovered {
+            id: website.monitor.id.clone(),
+            name: website.monitor.name.clone(),
+            url: website.monitor.url.clone(),
+            down_since,
+            recovered_at,
+            duration_secs: (recovered_at - down_since).num_seconds(),
+            last_error,
+        });
     }
 
-    let now = Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
-    let message = format!("Websites checked successfully at {}!", now);
-    println!("Check complete: {}", message);
+    let retry = website
+        .monitor
+        .confirmation
+        .retry_delay_secs
+        .filter(|_| assessment.awaiting_confirmation)
+        .map(Duration::from_secs);
+
+    drop(websites);
+    events::emit(
+        app_handle,
+        events::MONITOR_CHECKED,
+        &events::MonitorChecked {
+            check: history_record.clone(),
+            awaiting_confirmation: assessment.awaiting_confirmation,
+        },
+    );
+    if let Some(changed) = &state_changed {
+        events::emit(app_handle, events::MONITOR_STATE_CHANGED, changed);
+    }
+    if let Some(opened) = &incident_opened {
+        events::emit(app_handle, events::INCIDENT_OPENED, opened);
+    }
+    if let Some(resolved) = &incident_resolved {
+        events::emit(app_handle, events::INCIDENT_RESOLVED, resolved);
+    }
+    if let Some(recovered) = &website_recovered {
+        events::emit(app_handle, events::WEBSITE_RECOVERED, recovered);
+    }
+    for alert in alert.into_iter().chain(certificate_alerts) {
+        routing::route(app_handle, alert, alert_routes.as_deref(), notify_recovery);
+    }
+    if let Some(db) = &state.db {
+        if let Err(e) = history::record(db, &history_record) {
+            eprintln!("Failed to record check history: {}", e);
+        }
+        let latency = (observed != health::HealthState::Down && health != health::HealthState::Maintenance)
+            .then_some(history_record.latency_ms);
+        // the same clock as pause, offline and suspend records, so the cursor only moves forward
+        if let Err(e) = uptime::record_check(
+            db,
+            &history_record.monitor_id,
+            now,
+            &history_record.state,
+            latency,
+            max_gap_secs,
+        ) {
+            eprintln!("Failed to update uptime: {}", e);
+        }
+    }
+    if health != previous {
+        tray::refresh(app_handle);
+    }
+    retry
+}
 
-    let window = app_handle.get_webview_window("main").unwrap();
-    window
-        .emit("website_check_complete", message.clone())
-        .unwrap();
+// "1h 5m", "12m 30s", "45s"
+fn format_duration(total_secs: i64) -> String {
+    let total_secs = total_secs.max(0);
+    let (days, hours, minutes, seconds) = (
+        total_secs / 86_400,
+        total_secs % 86_400 / 3_600,
+        total_secs % 3_600 / 60,
+        total_secs % 60,
+    );
+    if days > 0 {
+        format!("{}d {}h", days, hours)
+    } else if hours > 0 {
+        format!("{}h {}m", hours, minutes)
+    } else if minutes > 0 {
+        format!("{}m {}s", minutes, seconds)
+    } else {
+        format!("{}s", seconds)
+    }
+}
 
-   Ok((websites, message))
+fn show_notification(app_handle: &AppHandle, title: &str, body: &str) {
+    let _ = app_handle
+        .notification()
+        .builder()
+        .title(title)
+        .body(body)
+        .show();
 }
 
 #[tauri::command]
@@ -368,115 +1820,144 @@ pub fn run() {
         .setup(|app| {
             #[cfg(target_os = "macos")]
             app.set_activation_policy(tauri::ActivationPolicy::Accessory);
-            // Create initial tray menu (assume window is visible, so "Hide")
-            let quit_i = MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?;
-            let show_i = MenuItem::with_id(app, "show", "Hide", true, None::<&str>)?;
-            let menu = Menu::with_items(app, &[&show_i, &quit_i])?;
-
-            // Create tray icon
-            let tray = TrayIconBuilder::new()
-                .icon(app.default_window_icon().unwrap().clone())
-                .menu(&menu)
-                .on_menu_event(|app, event| {
-                    if event.id == "quit" {
-                        app.exit(0);
-                    } else if event.id == "show" {
-                        let state = app.state::<AppState>();
-                        let window = app.get_webview_window("main").unwrap();
-                        let is_visible = window.is_visible().unwrap_or(false);
-                        if is_visible {
-                            // Hide window and update menu to "Show"
-                            window.hide().unwrap();
-                            let quit_i =
-                                MenuItem::with_id(app, "quit", "Quit", true, None::<&str>).unwrap();
-                            let show_i =
-                                MenuItem::with_id(app, "show", "Show", true, None::<&str>).unwrap();
-                            let menu = Menu::with_items(app, &[&show_i, &quit_i]).unwrap();
-                            state.tray.set_menu(Some(menu)).unwrap();
-                        } else {
-                            // Show window and update menu to "Hide"
-                            window.show().unwrap();
-                            window.set_focus().unwrap();
-                            let quit_i =
-                                MenuItem::with_id(app, "quit", "Quit", true, None::<&str>).unwrap();
-                            let show_i =
-                                MenuItem::with_id(app, "show", "Hide", true, None::<&str>).unwrap();
-                            let menu = Menu::with_items(app, &[&show_i, &quit_i]).unwrap();
-                            state.tray.set_menu(Some(menu)).unwrap();
+            let tray = tray::create(app)?;
+
+            // Load the saved monitor list; on first run seed it with the default site
+            let app_handle = app.handle().clone();
+            let initial_monitors = match monitors::load(&app_handle) {
+                Ok(Some(saved)) => saved,
+                Ok(None) => {
+                    let defaults = vec![monitors::Monitor::from_input(monitors::MonitorInput {
+                        url: "https://example.com".to_string(),
+                        ..Default::default()
+                    })?];
+                    if let Err(e) = monitors::save(&app_handle, &defaults) {
+                        eprintln!("Failed to save default monitors: {}", e);
+                    }
+                    defaults
+                }
+                Err(e) => {
+                    // start empty; the file is rewritten once the list is edited, so keep a copy
+                    match monitors::back_up(&app_handle) {
+                        Ok(backup) => eprintln!("Failed to load monitors: {} (copied to {})", e, backup.display()),
+                        Err(backup_error) => eprintln!("Failed to load monitors: {} ({})", e, backup_error),
+                    }
+                    Vec::new()
+                }
+            };
+            let mut initial_websites: Vec<Website> = initial_monitors.into_iter().map(Website::new).collect();
+            match tls::load_alert_states(&app_handle) {
+                Ok(mut saved) => {
+                    for website in initial_websites.iter_mut() {
+                        if let Some(alerted) = saved.remove(&website.monitor.id) {
+                            website.certificate_alerts = alerted;
+                        }
+                    }
+                }
+                Err(e) => eprintln!("Failed to load certificate state: {}", e),
+            }
+            let settings = settings::load(&app_handle);
+            let maintenance_windows = maintenance::load(&app_handle).unwrap_or_else(|e| {
+                eprintln!("Failed to load maintenance windows: {}", e);
+                Vec::new()
+            });
+            let channels = notifiers::load(&app_handle).unwrap_or_else(|e| {
+                eprintln!("Failed to load notification channels: {}", e);
+                vec![notifiers::desktop::channel()]
+            });
+            let routing_rules = routing::load(&app_handle).unwrap_or_else(|e| {
+                eprintln!("Failed to load routing rules: {}", e);
+                Vec::new()
+            });
+            let engine = checker::CheckEngine::new(&settings)?;
+            let db = match db::Database::open_for_app(&app_handle) {
+                Ok(db) => Some(db),
+                Err(e) => {
+                    eprintln!("Check history is unavailable: {}", e);
+                    None
+                }
+            };
+
+            let paused = match &db {
+                Some(db) => pauses::open_global(db).unwrap_or_else(|e| {
+                    eprintln!("Failed to load pause state: {}", e);
+                    None
+                }),
+                None => None,
+            };
+
+            // Monitors still caught up in an unresolved incident stay down until they recover,
+            // so the incident is closed by the next successful check rather than left open
+            if let Some(db) = &db {
+                match incidents::unresolved_by_monitor(db) {
+                    Ok(open) => {
+                        for website in initial_websites.iter_mut() {
+                            if let Some(incident) = open.get(&website.monitor.id) {
+                                website.state = health::HealthState::Down;
+                                website.state_reason = incident.last_error.clone();
+                                website.last_error = incident.last_error.clone();
+                                website.incident_id = Some(incident.id);
+                                website.down_since = incident
+                                    .monitors
+                                    .iter()
+                                    .find(|m| m.monitor_id == website.monitor.id)
+                                    .map(|m| m.joined_at);
+                            }
                         }
                     }
-                })
-                .on_tray_icon_event(|tray, event| {
-                    if let TrayIconEvent::Click {
-                        button: MouseButton::Left,
-                        button_state: MouseButtonState::Up,
-                        ..
-                    } = event
-                    {
-                        let app = tray.app_handle();
-                        let state = app.state::<AppState>();
-                        let window = app.get_webview_window("main").unwrap();
-                        window.show().unwrap();
-                        window.set_focus().unwrap();
-                        // Update menu to "Hide" after showing
-                        let quit_i =
-                            MenuItem::with_id(app, "quit", "Quit", true, None::<&str>).unwrap();
-                        let show_i =
-                            MenuItem::with_id(app, "show", "Hide", true, None::<&str>).unwrap();
-                        let menu = Menu::with_items(app, &[&show_i, &quit_i]).unwrap();
-                        state.tray.set_menu(Some(menu)).unwrap();
+                    Err(e) => eprintln!("Failed to load open incidents: {}", e),
+                }
+                match mutes::load_all(db) {
+                    Ok(saved) => {
+                        let now = Utc::now();
+                        for website in initial_websites.iter_mut() {
+                            if let Some(mute) = saved.get(&website.monitor.id) {
+                                // an acknowledgement only outlives a restart if the outage did
+                                website.notification_cleared =
+                                    mute.acknowledged && website.state == health::HealthState::Down;
+                                website.snoozed_until = mute.snoozed_until.filter(|until| *until > now);
+                            }
+                        }
                     }
-                })
-                .build(app)?;
-
-            let initial_websites = vec![
-                Website {
-                    url: "https://example.com".to_string(),
-                    is_down: false,
-                    last_checked: "2025-11-28".to_string(), // Use current date or a placeholder
-                    notification_cleared: false,
-                },
-                // Add more initial websites as needed
-            ];
+                    Err(e) => eprintln!("Failed to load notification state: {}", e),
+                }
+            }
 
             // Manage the tray in app state
             app.manage(AppState {
                 websites: Mutex::new(initial_websites),
+                settings: Mutex::new(settings),
+                maintenance: Mutex::new(maintenance_windows),
+                channels: Mutex::new(channels),
+                routing_rules: Mutex::new(routing_rules),
+                paused: Mutex::new(paused),
+                network: network::Network::default(),
+                clock: clock::Clock::default(),
+                engine: Mutex::new(Arc::new(engine)),
+                scheduler: Arc::new(scheduler::Scheduler::default()),
+                db,
                 tray,
                 refresher_tx: Mutex::new(None),
+                app_registration: Mutex::new(auth::AppRegistration::from_env()),
             });
 
-            // Spawn a background task to check websites every 60 seconds
-            let app_handle = app.handle().clone();
+            tray::refresh(&app_handle);
+
+            // Check each website on its own schedule in the background
+            let scheduler = Arc::clone(&app.state::<AppState>().scheduler);
             async_runtime::spawn(async move {
-                let mut interval = interval(Duration::from_secs(60));
-                loop {
-                    interval.tick().await;
-                    let state = app_handle.state::<AppState>();
-                    let websites_clone = state.websites.lock().unwrap().clone();
-                    if let Ok((updated_websites, _)) = do_check_websites(websites_clone, app_handle.clone()).await {
-                        *state.websites.lock().unwrap() = updated_websites;
-                    }
-                }
+                scheduler.run(app_handle).await;
             });
 
-
             Ok(())
         })
         .on_window_event(|window, event| {
             if let tauri::WindowEvent::CloseRequested { api, .. } = event {
-                window.hide().unwrap();
                 api.prevent_close();
-                // Update menu to "Show" after hiding
-                let app = window.app_handle();
-                let state = app.state::<AppState>();
-                let quit_i = MenuItem::with_id(app, "quit", "Quit", true, None::<&str>).unwrap();
-                let show_i = MenuItem::with_id(app, "show", "Show", true, None::<&str>).unwrap();
-                let menu = Menu::with_items(app, &[&show_i, &quit_i]).unwrap();
-                state.tray.set_menu(Some(menu)).unwrap();
+                tray::set_window_visible(window.app_handle(), false);
             }
         })
-        .invoke_handler(tauri::generate_handler![login, greet, check_websites, get_access_token, fetch_protected, logout, whoami, clear_last_user, get_env])
+        .invoke_handler(tauri::generate_handler![login, greet, check_websites, list_websites, add_website, update_website, remove_website, pause_monitoring, resume_monitoring, get_monitoring_status, get_network_status, pause_monitor, resume_monitor, list_paused_periods, acknowledge_monitor, snooze_monitor, get_settings, update_settings, get_check_history, get_uptime_report, list_channels, add_channel, update_channel, remove_channel, test_channel, get_delivery_log, list_routing_rules, add_routing_rule, update_routing_rule, move_routing_rule, remove_routing_rule, preview_routing, list_maintenance_windows, add_maintenance_window, update_maintenance_window, remove_maintenance_window, import_maintenance_ics, list_incidents, get_incident, acknowledge_incident, add_incident_note, get_access_token, fetch_protected, logout, whoami, clear_last_user, get_env])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
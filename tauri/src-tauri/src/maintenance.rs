@@ -0,0 +1,507 @@
+use chrono::{DateTime, Datelike, Duration, Local, NaiveTime, TimeZone, Utc, Weekday};
+use rrule::RRuleSet;
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+
+use crate::config;
+use crate::monitors::{self, Monitor};
+
+const MAINTENANCE_FILE: &str = "maintenance.json";
+const MAINTENANCE_FILE_VERSION: u32 = 1;
+// also bounds how far back the weekly lookup has to search
+const MAX_DURATION_MINS: u32 = 7 * 24 * 60;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MaintenanceWindow {
+    pub id: String,
+    pub name: String,
+    // the window covers these monitors plus every monitor in these groups
+    #[serde(default)]
+    pub monitor_ids: Vec<String>,
+    #[serde(default)]
+    pub groups: Vec<String>,
+    pub duration_mins: u32,
+    pub recurrence: Recurrence,
+}
+
+// When each occurrence of a window starts. Weekly and cron times are local time;
+// an RRULE carries its own DTSTART and time zone.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Recurrence {
+    Once { start: DateTime<Utc> },
+    // e.g. days ["Tue"], time "19:00"
+    Weekly { days: Vec<Weekday>, time: String },
+    Cron { expression: String },
+    // e.g. "DTSTART;TZID=Europe/Berlin:20240102T190000\nRRULE:FREQ=WEEKLY;BYDAY=TU"
+    Rrule { rule: String },
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct MaintenanceWindowInput {
+    pub name: String,
+    #[serde(default)]
+    pub monitor_ids: Vec<String>,
+    #[serde(default)]
+    pub groups: Vec<String>,
+    pub duration_mins: u32,
+    pub recurrence: Recurrence,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct MaintenanceFile {
+    windows: Vec<MaintenanceWindow>,
+}
+
+impl Recurrence {
+    fn validate(&self) -> Result<(), String> {
+        match self {
+            Recurrence::Once { .. } => Ok(()),
+            Recurrence::Weekly { days, time } => {
+                if days.is_empty() {
+                    return Err("a weekly window needs at least one day".to_string());
+                }
+                parse_time_of_day(time).map(|_| ())
+            }
+            Recurrence::Cron { expression } => monitors::parse_cron(expression).map(|_| ()),
+            Recurrence::Rrule { rule } => parse_rrule(rule).map(|_| ()),
+        }
+    }
+
+    // Whether an occurrence lasting `duration` covers `now`
+    fn covers(&self, duration: Duration, now: DateTime<Utc>) -> bool {
+        let earliest = now - duration;
+        match self {
+            Recurrence::Once { start } => *start <= now && now < *start + duration,
+            Recurrence::Weekly { days, time } => {
+                let Ok(time) = parse_time_of_day(time) else {
+                    return false;
+                };
+                let today = now.with_timezone(&Local).date_naive();
+                (0..=7).filter_map(|back| today.checked_sub_days(chrono::Days::new(back))).any(|date| {
+                    days.contains(&date.weekday())
+                        && Local
+                            .from_local_datetime(&date.and_time(time))
+                            .earliest()
+                            .map(|start| start.with_timezone(&Utc))
+                            .is_some_and(|start| start <= now && earliest < start)
+                })
+            }
+            Recurrence::Cron { expression } => monitors::parse_cron(expression)
+                .ok()
+                .and_then(|schedule| schedule.after(&earliest.with_timezone(&Local)).next())
+                .is_some_and(|start| start.with_timezone(&Utc) <= now),
+            // an occurrence starting exactly now is covered, one that started exactly `duration` ago
+            // has just ended; checked here rather than relying on how the crate treats its bounds
+            Recurrence::Rrule { rule } => parse_rrule(rule).is_ok_and(|set| {
+                let tz = set.get_dt_start().timezone();
+                set.after(earliest.with_timezone(&tz))
+                    .before(now.with_timezone(&tz))
+                    .all(2)
+                    .dates
+                    .iter()
+                    .map(|start| start.with_timezone(&Utc))
+                    .any(|start| earliest < start && start <= now)
+            }),
+        }
+    }
+}
+
+impl MaintenanceWindow {
+    pub fn from_input(input: MaintenanceWindowInput) -> Result<MaintenanceWindow, String> {
+        let mut window = MaintenanceWindow {
+            id: monitors::new_id(),
+            name: String::new(),
+            monitor_ids: Vec::new(),
+            groups: Vec::new(),
+            duration_mins: 0,
+            recurrence: Recurrence::Once { start: Utc::now() },
+        };
+        window.apply(input)?;
+        Ok(window)
+    }
+
+    pub fn apply(&mut self, input: MaintenanceWindowInput) -> Result<(), String> {
+        let name = input.name.trim();
+        if name.is_empty() {
+            return Err("maintenance window needs a name".to_string());
+        }
+        let monitor_ids: Vec<String> = input
+            .monitor_ids
+            .iter()
+            .map(|id| id.trim().to_string())
+            .filter(|id| !id.is_empty())
+            .collect();
+        let groups: Vec<String> = input
+            .groups
+            .iter()
+            .map(|g| g.trim().to_string())
+            .filter(|g| !g.is_empty())
+            .collect();
+        if monitor_ids.is_empty() && groups.is_empty() {
+            return Err("maintenance window must cover at least one monitor or group".to_string());
+        }
+        if input.duration_mins == 0 || input.duration_mins > MAX_DURATION_MINS {
+            return Err(format!("duration must be between 1 and {} minutes", MAX_DURATION_MINS));
+        }
+        input.recurrence.validate()?;
+
+        self.name = name.to_string();
+        self.monitor_ids = monitor_ids;
+        self.groups = groups;
+        self.duration_mins = input.duration_mins;
+        self.recurrence = input.recurrence;
+        Ok(())
+    }
+
+    pub fn applies_to(&self, monitor: &Monitor) -> bool {
+        self.monitor_ids.contains(&monitor.id)
+            || monitor.group.as_ref().is_some_and(|group| self.groups.contains(group))
+    }
+
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.recurrence.covers(Duration::minutes(self.duration_mins as i64), now)
+    }
+}
+
+// The window currently covering this monitor, if any
+pub fn active_for<'a>(
+    windows: &'a [MaintenanceWindow],
+    monitor: &Monitor,
+    now: DateTime<Utc>,
+) -> Option<&'a MaintenanceWindow> {
+    windows.iter().find(|w| w.applies_to(monitor) && w.is_active(now))
+}
+
+pub fn parse_time_of_day(time: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(time.trim(), "%H:%M")
+        .map_err(|_| format!("invalid time of day '{}' (use HH:MM)", time))
+}
+
+fn parse_rrule(rule: &str) -> Result<RRuleSet, String> {
+    rule.trim()
+        .parse::<RRuleSet>()
+        .map_err(|e| format!("invalid RRULE: {}", e))
+}
+
+// What an .ics import produced: the new windows, and the events that couldn't be used and why
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct IcsImport {
+    pub windows: Vec<MaintenanceWindow>,
+    pub skipped: Vec<SkippedEvent>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct SkippedEvent {
+    // the event's SUMMARY, or its DTSTART when it has none
+    pub event: String,
+    pub error: String,
+}
+
+// Turns the VEVENTs of an iCalendar file into maintenance windows for the given targets.
+// Recurring events keep their RRULE; one-off events that have already ended are left out.
+// An event that can't be imported is reported and the rest still are.
+pub fn import_ics(contents: &str, monitor_ids: Vec<String>, groups: Vec<String>) -> Result<IcsImport, String> {
+    // continuation lines start with a space or tab
+    let mut lines: Vec<String> = Vec::new();
+    for line in contents.lines() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some(previous)) => previous.push_str(rest),
+            _ => lines.push(line.trim_end().to_string()),
+        }
+    }
+
+    let mut import = IcsImport::default();
+    let mut event: Option<Vec<(String, String)>> = None;
+    for line in lines {
+        if line.eq_ignore_ascii_case("BEGIN:VEVENT") {
+            event = Some(Vec::new());
+        } else if line.eq_ignore_ascii_case("END:VEVENT") {
+            if let Some(properties) = event.take() {
+                let window = event_to_input(&properties, &monitor_ids, &groups)
+                    .and_then(|input| input.map(MaintenanceWindow::from_input).transpose());
+                match window {
+                    Ok(Some(window)) => import.windows.push(window),
+                    Ok(None) => {}
+                    Err(error) => import.skipped.push(SkippedEvent {
+                        event: event_label(&properties),
+                        error,
+                    }),
+                }
+            }
+        } else if let (Some(properties), Some((name, _))) = (event.as_mut(), line.split_once(':')) {
+            // the property name ends at the first ';' (parameters) or ':' (value)
+            let key = name.split(';').next().unwrap_or(name).to_ascii_uppercase();
+            properties.push((key, line));
+        }
+    }
+    if import.windows.is_empty() && import.skipped.is_empty() {
+        return Err("no upcoming events found in the calendar".to_string());
+    }
+    Ok(import)
+}
+
+fn event_label(properties: &[(String, String)]) -> String {
+    let value = |key: &str| {
+        properties
+            .iter()
+            .find(|(k, _)| k == key)
+            .and_then(|(_, line)| line.split_once(':'))
+            .map(|(_, v)| unescape_text(v))
+            .filter(|v| !v.trim().is_empty())
+    };
+    value("SUMMARY")
+        .or_else(|| value("DTSTART"))
+        .unwrap_or_else(|| "unnamed event".to_string())
+}
+
+fn event_to_input(
+    properties: &[(String, String)],
+    monitor_ids: &[String],
+    groups: &[String],
+) -> Result<Option<MaintenanceWindowInput>, String> {
+    let property = |key: &str| properties.iter().find(|(k, _)| k == key).map(|(_, line)| line.as_str());
+    let value = |line: &str| line.split_once(':').map(|(_, v)| v.to_string()).unwrap_or_default();
+
+    let Some(dtstart) = property("DTSTART") else {
+        return Ok(None);
+    };
+    let start = ics_time(dtstart)?;
+    let duration = match (property("DTEND"), property("DURATION")) {
+        (Some(dtend), _) => ics_time(&dtend.replacen("DTEND", "DTSTART", 1))? - start,
+        (None, Some(duration)) => ics_duration(&value(duration))?,
+        // all-day events with neither last the whole day
+        (None, None) if dtstart.contains("VALUE=DATE") => Duration::days(1),
+        (None, None) => return Err(format!("event starting {} has no end or duration", start.to_rfc3339())),
+    };
+    if duration <= Duration::zero() {
+        return Err(format!("event starting {} does not end after it starts", start.to_rfc3339()));
+    }
+    // partial minutes round up
+    let duration_mins = u32::try_from((duration.num_seconds() + 59) / 60)
+        .map_err(|_| format!("event starting {} is too long", start.to_rfc3339()))?;
+    let name = property("SUMMARY")
+        .map(|line| unescape_text(&value(line)))
+        .filter(|s| !s.trim().is_empty())
+        .unwrap_or_else(|| "Imported maintenance".to_string());
+
+    let recurrence = match property("RRULE") {
+        Some(rrule) => {
+            let mut rule = format!("{}\n{}", dtstart, rrule);
+            for (key, line) in properties {
+                if key == "EXDATE" {
+                    rule.push('\n');
+                    rule.push_str(line);
+                }
+            }
+            Recurrence::Rrule { rule }
+        }
+        None if start + duration <= Utc::now() => return Ok(None),
+        None => Recurrence::Once { start },
+    };
+    Ok(Some(MaintenanceWindowInput {
+        name,
+        monitor_ids: monitor_ids.to_vec(),
+        groups: groups.to_vec(),
+        duration_mins,
+        recurrence,
+    }))
+}
+
+// Resolves a DTSTART line (UTC, TZID or floating local time) to an instant. The rrule
+// parser already understands all of these, so give it a rule that yields just the start.
+fn ics_time(dtstart: &str) -> Result<DateTime<Utc>, String> {
+    let set = parse_rrule(&format!("{}\nRRULE:FREQ=DAILY;COUNT=1", dtstart))?;
+    Ok(set.get_dt_start().with_timezone(&Utc))
+}
+
+// ISO 8601 durations as used by iCalendar: P1W, P1D, PT1H30M, P1DT2H. There are no months,
+// so M is only minutes, after the T.
+fn ics_duration(value: &str) -> Result<Duration, String> {
+    let invalid = || format!("invalid duration '{}'", value);
+    let rest = value.trim().trim_start_matches('+').strip_prefix('P').ok_or_else(invalid)?;
+    let mut total = Duration::zero();
+    let mut number = String::new();
+    let mut time = false;
+    for c in rest.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'T' if !time => time = true,
+            unit => {
+                let n: i64 = number.parse().map_err(|_| invalid())?;
+                number.clear();
+                let part = match (unit, time) {
+                    ('W', false) => Duration::try_weeks(n),
+                    ('D', false) => Duration::try_days(n),
+                    ('H', true) => Duration::try_hours(n),
+                    ('M', true) => Duration::try_minutes(n),
+                    ('S', true) => Duration::try_seconds(n),
+                    _ => None,
+                };
+                total = part.and_then(|part| total.checked_add(&part)).ok_or_else(invalid)?;
+            }
+        }
+    }
+    if !number.is_empty() {
+        return Err(invalid());
+    }
+    Ok(total)
+}
+
+fn unescape_text(value: &str) -> String {
+    value
+        .replace("\\n", " ")
+        .replace("\\N", " ")
+        .replace("\\,", ",")
+        .replace("\\;", ";")
+        .replace("\\\\", "\\")
+}
+
+pub fn load(app_handle: &AppHandle) -> Result<Vec<MaintenanceWindow>, String> {
+    let path = config::config_path(app_handle, MAINTENANCE_FILE)?;
+    let file: Option<MaintenanceFile> = config::load_versioned(&path, MAINTENANCE_FILE_VERSION)?;
+    Ok(file.unwrap_or_default().windows)
+}
+
+pub fn save(app_handle: &AppHandle, windows: &[MaintenanceWindow]) -> Result<(), String> {
+    let path = config::config_path(app_handle, MAINTENANCE_FILE)?;
+    let file = MaintenanceFile {
+        windows: windows.to_vec(),
+    };
+    config::save_versioned(&path, MAINTENANCE_FILE_VERSION, &file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the property list import_ics builds for one VEVENT
+    fn properties(lines: &[&str]) -> Vec<(String, String)> {
+        lines
+            .iter()
+            .map(|line| {
+                let name = line.split([';', ':']).next().unwrap();
+                (name.to_string(), line.to_string())
+            })
+            .collect()
+    }
+
+    fn input(lines: &[&str]) -> Result<Option<MaintenanceWindowInput>, String> {
+        event_to_input(&properties(lines), &["site".to_string()], &[])
+    }
+
+    fn utc(value: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(value).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn ics_durations_add_up_their_parts() {
+        assert_eq!(ics_duration("P1W"), Ok(Duration::weeks(1)));
+        assert_eq!(ics_duration("P1D"), Ok(Duration::days(1)));
+        assert_eq!(ics_duration("PT1H30M"), Ok(Duration::minutes(90)));
+        assert_eq!(ics_duration("+P1DT2H"), Ok(Duration::hours(26)));
+        assert_eq!(ics_duration("PT45S"), Ok(Duration::seconds(45)));
+    }
+
+    #[test]
+    fn bad_ics_durations_are_rejected() {
+        // P1M would be a month in ISO 8601, which iCalendar doesn't allow
+        for value in ["", "1D", "P1M", "P1H", "PT1D", "PT5", "P1.5D", "-P1D", "P1Y", "PTT1H"] {
+            assert!(ics_duration(value).is_err(), "{} was accepted", value);
+        }
+        // too big for chrono, not a panic
+        assert!(ics_duration("P99999999999999W").is_err());
+        assert!(ics_duration("P9999999999999DT9999999999999H").is_err());
+    }
+
+    #[test]
+    fn events_take_their_length_from_dtend_or_duration() {
+        let window = input(&[
+            "SUMMARY:Patching",
+            "DTSTART:20990105T190000Z",
+            "DTEND:20990105T203000Z",
+        ])
+        .unwrap()
+        .unwrap();
+        assert_eq!(window.name, "Patching");
+        assert_eq!(window.duration_mins, 90);
+        assert!(matches!(window.recurrence, Recurrence::Once { start } if start == utc("2099-01-05T19:00:00Z")));
+
+        let window = input(&["DTSTART:20990105T190000Z", "DURATION:PT2H"]).unwrap().unwrap();
+        assert_eq!(window.name, "Imported maintenance");
+        assert_eq!(window.duration_mins, 120);
+    }
+
+    #[test]
+    fn all_day_events_last_a_day() {
+        let window = input(&["DTSTART;VALUE=DATE:20990105"]).unwrap().unwrap();
+        assert_eq!(window.duration_mins, 24 * 60);
+    }
+
+    #[test]
+    fn recurring_events_keep_their_rule() {
+        let window = input(&[
+            "DTSTART:20200107T190000Z",
+            "DURATION:PT1H",
+            "RRULE:FREQ=WEEKLY;BYDAY=TU",
+            "EXDATE:20200114T190000Z",
+        ])
+        .unwrap()
+        .unwrap();
+        let Recurrence::Rrule { rule } = window.recurrence else {
+            panic!("expected an RRULE");
+        };
+        assert_eq!(
+            rule,
+            "DTSTART:20200107T190000Z\nRRULE:FREQ=WEEKLY;BYDAY=TU\nEXDATE:20200114T190000Z"
+        );
+    }
+
+    #[test]
+    fn past_one_off_events_are_left_out() {
+        assert!(input(&["DTSTART:20200105T190000Z", "DURATION:PT1H"]).unwrap().is_none());
+    }
+
+    #[test]
+    fn events_that_end_too_early_or_too_late_are_errors() {
+        assert!(input(&["DTSTART:20990105T190000Z", "DTEND:20990105T180000Z"]).is_err());
+        assert!(input(&["DTSTART:20990105T190000Z", "DTEND:20990105T190000Z"]).is_err());
+        assert!(input(&["DTSTART:20990105T190000Z", "DURATION:P99999999D"]).is_err());
+        assert!(input(&["DTSTART:20990105T190000Z"]).is_err());
+    }
+
+    #[test]
+    fn unusable_events_are_skipped_and_the_rest_imported() {
+        let ics = "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nSUMMARY:Backwards\r\nDTSTART:20990105T190000Z\r\n\
+                   DTEND:20990105T180000Z\r\nEND:VEVENT\r\nBEGIN:VEVENT\r\nSUMMARY:Patching\r\n\
+                   DTSTART:20990105T190000Z\r\nDURATION:PT1H\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n";
+        let import = import_ics(ics, vec!["site".to_string()], Vec::new()).unwrap();
+        assert_eq!(import.windows.len(), 1);
+        assert_eq!(import.windows[0].name, "Patching");
+        assert_eq!(import.skipped.len(), 1);
+        assert_eq!(import.skipped[0].event, "Backwards");
+    }
+
+    #[test]
+    fn rrule_windows_follow_local_time_across_a_dst_change() {
+        // 19:00 in Berlin is 18:00 UTC in winter and 17:00 UTC once summer time starts (31 March 2024)
+        let recurrence = Recurrence::Rrule {
+            rule: "DTSTART;TZID=Europe/Berlin:20240319T190000\nRRULE:FREQ=WEEKLY;BYDAY=TU".to_string(),
+        };
+        let hour = Duration::hours(1);
+        assert!(recurrence.covers(hour, utc("2024-03-26T18:30:00Z")));
+        assert!(!recurrence.covers(hour, utc("2024-03-26T17:30:00Z")));
+        assert!(recurrence.covers(hour, utc("2024-04-02T17:30:00Z")));
+        assert!(!recurrence.covers(hour, utc("2024-04-02T18:30:00Z")));
+    }
+
+    #[test]
+    fn occurrences_include_their_start_but_not_their_end() {
+        let recurrence = Recurrence::Once {
+            start: utc("2024-03-26T18:00:00Z"),
+        };
+        let hour = Duration::hours(1);
+        assert!(recurrence.covers(hour, utc("2024-03-26T18:00:00Z")));
+        assert!(!recurrence.covers(hour, utc("2024-03-26T19:00:00Z")));
+    }
+}
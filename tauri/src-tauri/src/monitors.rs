@@ -0,0 +1,569 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::path::PathBuf;
+use std::str::FromStr;
+use tauri::AppHandle;
+use url::Url;
+
+use crate::assertions::Assertion;
+use crate::config;
+
+const MONITORS_FILE: &str = "monitors.json";
+const MONITORS_FILE_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Monitor {
+    pub id: String,
+    pub name: String,
+    pub url: String,
+    #[serde(default)]
+    pub group: Option<String>,
+    // availability target in percent, e.g. 99.9
+    #[serde(default)]
+    pub sla_target: Option<f64>,
+    #[serde(default)]
+    pub schedule: Schedule,
+    #[serde(default)]
+    pub thresholds: LatencyThresholds,
+    #[serde(default)]
+    pub assertions: Vec<Assertion>,
+    #[serde(default)]
+    pub request: RequestSpec,
+    // overrides the app-wide certificate expiry warnings for this monitor
+    #[serde(default)]
+    pub certificate_warning_days: Option<Vec<u32>>,
+    #[serde(default)]
+    pub confirmation: Confirmation,
+    #[serde(default)]
+    pub flap_detection: FlapDetection,
+    // overrides the app-wide recovery notification setting for this monitor
+    #[serde(default)]
+    pub notify_recovery: Option<bool>,
+    // alert channels this monitor routes to; None means every enabled channel
+    #[serde(default)]
+    pub channels: Option<Vec<String>>,
+    // free-form labels that alert routing rules can match on, e.g. "production"
+    #[serde(default)]
+    pub tags: Vec<String>,
+    // paused monitors are not checked
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    // a paused monitor resumes by itself at this time
+    #[serde(default)]
+    pub paused_until: Option<DateTime<Utc>>,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+// How many results in a row it takes to believe a state change
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Confirmation {
+    pub failures_before_down: u32,
+    pub successes_before_up: u32,
+    // re-check this soon after an unconfirmed failure instead of waiting for the next slot
+    pub retry_delay_secs: Option<u64>,
+}
+
+impl Default for Confirmation {
+    fn default() -> Self {
+        Confirmation {
+            failures_before_down: 1,
+            successes_before_up: 1,
+            retry_delay_secs: None,
+        }
+    }
+}
+
+const MAX_RETRY_DELAY_SECS: u64 = 60;
+
+impl Confirmation {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.failures_before_down == 0 || self.successes_before_up == 0 {
+            return Err("confirmation counts must be at least 1".to_string());
+        }
+        if let Some(delay) = self.retry_delay_secs {
+            if delay == 0 || delay > MAX_RETRY_DELAY_SECS {
+                return Err(format!("retry delay must be between 1 and {} seconds", MAX_RETRY_DELAY_SECS));
+            }
+        }
+        Ok(())
+    }
+}
+
+// A monitor that changes state `max_changes` times within `window_secs` is flapping
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct FlapDetection {
+    // None turns flap detection off
+    pub max_changes: Option<u32>,
+    pub window_secs: u64,
+}
+
+impl Default for FlapDetection {
+    fn default() -> Self {
+        FlapDetection {
+            max_changes: None,
+            window_secs: 600,
+        }
+    }
+}
+
+impl FlapDetection {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_changes.map(|m| m < 2).unwrap_or(false) {
+            return Err("flap detection needs at least 2 state changes".to_string());
+        }
+        if self.window_secs < 60 {
+            return Err("flap window must be at least 60 seconds".to_string());
+        }
+        Ok(())
+    }
+}
+
+// How the check request is made and which responses count as healthy
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct RequestSpec {
+    pub method: String,
+    pub headers: BTreeMap<String, String>,
+    pub body: Option<String>,
+    // None uses the engine's default timeout
+    pub timeout_secs: Option<u64>,
+    // empty means any 2xx
+    pub accepted_statuses: Vec<StatusRange>,
+    // turn off to check the redirect itself, e.g. to accept a 302 to a login page
+    pub follow_redirects: bool,
+}
+
+impl Default for RequestSpec {
+    fn default() -> Self {
+        RequestSpec {
+            method: "GET".to_string(),
+            headers: BTreeMap::new(),
+            body: None,
+            timeout_secs: None,
+            accepted_statuses: Vec::new(),
+            follow_redirects: true,
+        }
+    }
+}
+
+const MAX_TIMEOUT_SECS: u64 = 300;
+
+impl RequestSpec {
+    pub fn validate(&self) -> Result<(), String> {
+        reqwest::Method::from_bytes(self.method.trim().to_uppercase().as_bytes())
+            .map_err(|_| format!("invalid HTTP method '{}'", self.method))?;
+        for (name, value) in &self.headers {
+            reqwest::header::HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| format!("invalid header name '{}'", name))?;
+            reqwest::header::HeaderValue::from_str(value)
+                .map_err(|_| format!("invalid value for header '{}'", name))?;
+        }
+        if let Some(timeout) = self.timeout_secs {
+            if timeout == 0 || timeout > MAX_TIMEOUT_SECS {
+                return Err(format!("timeout must be between 1 and {} seconds", MAX_TIMEOUT_SECS));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn accepts(&self, status: u16) -> bool {
+        if self.accepted_statuses.is_empty() {
+            return (200..300).contains(&status);
+        }
+        self.accepted_statuses.iter().any(|r| r.contains(status))
+    }
+}
+
+// An accepted status code or inclusive range, written as 401, "401", "200-299" or "3xx"
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(try_from = "StatusRangeRepr", into = "String")]
+pub struct StatusRange {
+    pub from: u16,
+    pub to: u16,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StatusRangeRepr {
+    Code(u16),
+    Text(String),
+}
+
+impl StatusRange {
+    pub fn contains(&self, status: u16) -> bool {
+        (self.from..=self.to).contains(&status)
+    }
+}
+
+impl TryFrom<StatusRangeRepr> for StatusRange {
+    type Error = String;
+
+    fn try_from(repr: StatusRangeRepr) -> Result<Self, Self::Error> {
+        let text = match repr {
+            StatusRangeRepr::Code(code) => code.to_string(),
+            StatusRangeRepr::Text(text) => text.trim().to_lowercase(),
+        };
+        let code = |s: &str| {
+            s.trim()
+                .parse::<u16>()
+                .ok()
+                .filter(|c| (100..=599).contains(c))
+                .ok_or_else(|| format!("invalid status code '{}'", s))
+        };
+        let range = if let Some(class) = text.strip_suffix("xx") {
+            let first = code(&format!("{}00", class))?;
+            StatusRange { from: first, to: first + 99 }
+        } else if let Some((from, to)) = text.split_once('-') {
+            StatusRange { from: code(from)?, to: code(to)? }
+        } else {
+            let single = code(&text)?;
+            StatusRange { from: single, to: single }
+        };
+        if range.from > range.to {
+            return Err(format!("status range '{}' is backwards", text));
+        }
+        Ok(range)
+    }
+}
+
+impl From<StatusRange> for String {
+    fn from(range: StatusRange) -> String {
+        if range.from == range.to {
+            range.from.to_string()
+        } else {
+            format!("{}-{}", range.from, range.to)
+        }
+    }
+}
+
+// Response times at or above the warning threshold mark the monitor as degraded; at or above
+// the critical one the check counts as failed, like an error
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct LatencyThresholds {
+    pub warning_ms: Option<u64>,
+    pub critical_ms: Option<u64>,
+}
+
+impl LatencyThresholds {
+    pub fn validate(&self) -> Result<(), String> {
+        if let (Some(warning), Some(critical)) = (self.warning_ms, self.critical_ms) {
+            if warning > critical {
+                return Err("warning threshold must not be above the critical threshold".to_string());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Schedule {
+    pub interval_secs: u64,
+    // standard 5-field (or 6-field with seconds) cron expression in local time; overrides interval_secs
+    pub cron: Option<String>,
+    // random delay of up to this many seconds before the first check after startup
+    pub jitter_secs: u64,
+}
+
+impl Default for Schedule {
+    fn default() -> Self {
+        Schedule {
+            interval_secs: 60,
+            cron: None,
+            jitter_secs: 0,
+        }
+    }
+}
+
+const MIN_INTERVAL_SECS: u64 = 5;
+
+impl Schedule {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.interval_secs < MIN_INTERVAL_SECS {
+            return Err(format!("interval must be at least {} seconds", MIN_INTERVAL_SECS));
+        }
+        if self.cron.is_some() {
+            self.cron_schedule()?;
+        }
+        Ok(())
+    }
+
+    // Typical time between checks; for cron schedules, the gap between the next two runs
+    pub fn nominal_interval_secs(&self) -> u64 {
+        if let Ok(Some(cron)) = self.cron_schedule() {
+            let mut upcoming = cron.upcoming(chrono::Utc);
+            if let (Some(a), Some(b)) = (upcoming.next(), upcoming.next()) {
+                return (b - a).num_seconds().max(1) as u64;
+            }
+        }
+        self.interval_secs
+    }
+
+    pub fn cron_schedule(&self) -> Result<Option<cron::Schedule>, String> {
+        match self.cron.as_deref().map(str::trim).filter(|e| !e.is_empty()) {
+            Some(expression) => parse_cron(expression).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+pub fn parse_cron(expression: &str) -> Result<cron::Schedule, String> {
+    // the cron crate wants a seconds field; accept the usual 5-field form too
+    let expression = expression.trim();
+    let expression = if expression.split_whitespace().count() == 5 {
+        format!("0 {}", expression)
+    } else {
+        expression.to_string()
+    };
+    cron::Schedule::from_str(&expression).map_err(|e| format!("invalid cron expression '{}': {}", expression, e))
+}
+
+// Fields the frontend sends when adding or updating a monitor
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct MonitorInput {
+    pub url: String,
+    pub name: Option<String>,
+    pub group: Option<String>,
+    pub sla_target: Option<f64>,
+    pub schedule: Option<Schedule>,
+    pub thresholds: Option<LatencyThresholds>,
+    pub assertions: Option<Vec<Assertion>>,
+    pub request: Option<RequestSpec>,
+    pub certificate_warning_days: Option<Vec<u32>>,
+    pub confirmation: Option<Confirmation>,
+    pub flap_detection: Option<FlapDetection>,
+    pub notify_recovery: Option<bool>,
+    pub channels: Option<Vec<String>>,
+    pub tags: Option<Vec<String>>,
+    pub enabled: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct MonitorsFile {
+    monitors: Vec<Monitor>,
+}
+
+impl Monitor {
+    pub fn from_input(input: MonitorInput) -> Result<Monitor, String> {
+        let mut monitor = Monitor {
+            id: new_id(),
+            name: String::new(),
+            url: String::new(),
+            group: None,
+            sla_target: None,
+            schedule: Schedule::default(),
+            thresholds: LatencyThresholds::default(),
+            assertions: Vec::new(),
+            request: RequestSpec::default(),
+            certificate_warning_days: None,
+            confirmation: Confirmation::default(),
+            flap_detection: FlapDetection::default(),
+            notify_recovery: None,
+            channels: None,
+            tags: Vec::new(),
+            enabled: true,
+            paused_until: None,
+        };
+        monitor.apply(input)?;
+        Ok(monitor)
+    }
+
+    pub fn apply(&mut self, input: MonitorInput) -> Result<(), String> {
+        let url = normalize_url(&input.url)?;
+        if let Some(schedule) = &input.schedule {
+            schedule.validate()?;
+        }
+        if let Some(target) = input.sla_target {
+            if !(0.0..100.0).contains(&target) {
+                return Err("SLA target must be at least 0 and below 100 percent".to_string());
+            }
+        }
+        if let Some(thresholds) = &input.thresholds {
+            thresholds.validate()?;
+        }
+        for assertion in input.assertions.iter().flatten() {
+            assertion.validate()?;
+        }
+        if let Some(request) = &input.request {
+            request.validate()?;
+        }
+        if let Some(confirmation) = &input.confirmation {
+            confirmation.validate()?;
+        }
+        if let Some(flap_detection) = &input.flap_detection {
+            flap_detection.validate()?;
+        }
+        // a missing name keeps the current one; an empty one goes back to the hostname
+        self.name = match input.name.map(|n| n.trim().to_string()) {
+            Some(name) if !name.is_empty() => name,
+            None if !self.name.is_empty() => self.name.clone(),
+            _ => Url::parse(&url)
+                .ok()
+                .and_then(|u| u.host_str().map(|h| h.to_string()))
+                .unwrap_or_else(|| url.clone()),
+        };
+        self.url = url;
+        if let Some(group) = input.group {
+            let group = group.trim().to_string();
+            self.group = if group.is_empty() { None } else { Some(group) };
+        }
+        if input.sla_target.is_some() {
+            self.sla_target = input.sla_target;
+        }
+        if let Some(schedule) = input.schedule {
+            self.schedule = schedule;
+        }
+        if let Some(thresholds) = input.thresholds {
+            self.thresholds = thresholds;
+        }
+        if let Some(assertions) = input.assertions {
+            self.assertions = assertions;
+        }
+        if let Some(confirmation) = input.confirmation {
+            self.confirmation = confirmation;
+        }
+        if let Some(flap_detection) = input.flap_detection {
+            self.flap_detection = flap_detection;
+        }
+        if input.notify_recovery.is_some() {
+            self.notify_recovery = input.notify_recovery;
+        }
+        if input.certificate_warning_days.is_some() {
+            self.certificate_warning_days = input.certificate_warning_days;
+        }
+        if let Some(channels) = input.channels {
+            // each channel once, in the order given
+            let mut seen = HashSet::new();
+            let ids: Vec<String> = channels
+                .iter()
+                .map(|id| id.trim().to_string())
+                .filter(|id| !id.is_empty() && seen.insert(id.clone()))
+                .collect();
+            self.channels = Some(ids);
+        }
+        if let Some(enabled) = input.enabled {
+            self.enabled = enabled;
+            if enabled {
+                self.paused_until = None;
+            }
+        }
+        if let Some(tags) = input.tags {
+            let mut tags: Vec<String> = tags
+                .iter()
+                .map(|tag| tag.trim().to_lowercase())
+                .filter(|tag| !tag.is_empty())
+                .collect();
+            tags.sort();
+            tags.dedup();
+            self.tags = tags;
+        }
+        if let Some(mut request) = input.request {
+            request.method = request.method.trim().to_uppercase();
+            self.request = request;
+        }
+        Ok(())
+    }
+}
+
+pub fn new_id() -> String {
+    format!("{:016x}", rand::random::<u64>())
+}
+
+// Accepts "example.com", "HTTPS://Example.com:443/#top" etc. and returns a canonical http(s) URL
+pub fn normalize_url(input: &str) -> Result<String, String> {
+    let trimmed = input.trim();
+    if trimmed.is_empty() {
+        return Err("URL must not be empty".to_string());
+    }
+    let with_scheme = if trimmed.contains("://") {
+        trimmed.to_string()
+    } else {
+        format!("https://{}", trimmed)
+    };
+    let mut url = Url::parse(&with_scheme).map_err(|e| format!("invalid URL '{}': {}", trimmed, e))?;
+    if url.scheme() != "http" && url.scheme() != "https" {
+        return Err(format!("unsupported scheme '{}': only http and https can be monitored", url.scheme()));
+    }
+    if url.host_str().map(|h| h.is_empty()).unwrap_or(true) {
+        return Err(format!("URL '{}' has no host", trimmed));
+    }
+    if !url.username().is_empty() || url.password().is_some() {
+        return Err("credentials in the URL are not supported".to_string());
+    }
+    url.set_fragment(None);
+    Ok(url.to_string())
+}
+
+pub fn ensure_unique(monitors: &[Monitor], url: &str, ignore_id: Option<&str>) -> Result<(), String> {
+    match monitors
+        .iter()
+        .find(|m| m.url == url && Some(m.id.as_str()) != ignore_id)
+    {
+        Some(existing) => Err(format!("{} is already monitored as '{}'", url, existing.name)),
+        None => Ok(()),
+    }
+}
+
+pub fn load(app_handle: &AppHandle) -> Result<Option<Vec<Monitor>>, String> {
+    let path = config::config_path(app_handle, MONITORS_FILE)?;
+    let file: Option<MonitorsFile> = config::load_versioned(&path, MONITORS_FILE_VERSION)?;
+    Ok(file.map(|f| f.monitors))
+}
+
+// Copies an unreadable monitors.json aside, so editing the list can't overwrite it
+pub fn back_up(app_handle: &AppHandle) -> Result<PathBuf, String> {
+    let path = config::config_path(app_handle, MONITORS_FILE)?;
+    let backup = path.with_extension("json.bak");
+    std::fs::copy(&path, &backup).map_err(|e| format!("failed to copy {}: {}", path.display(), e))?;
+    Ok(backup)
+}
+
+pub fn save(app_handle: &AppHandle, monitors: &[Monitor]) -> Result<(), String> {
+    let path = config::config_path(app_handle, MONITORS_FILE)?;
+    let file = MonitorsFile {
+        monitors: monitors.to_vec(),
+    };
+    config::save_versioned(&path, MONITORS_FILE_VERSION, &file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(value: serde_json::Value) -> Result<StatusRange, String> {
+        serde_json::from_value(value).map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn status_ranges_parse_codes_ranges_and_classes() {
+        assert_eq!(range(serde_json::json!(401)).unwrap(), StatusRange { from: 401, to: 401 });
+        assert_eq!(range(serde_json::json!(" 404 ")).unwrap(), StatusRange { from: 404, to: 404 });
+        assert_eq!(range(serde_json::json!("200-299")).unwrap(), StatusRange { from: 200, to: 299 });
+        assert_eq!(range(serde_json::json!("3xx")).unwrap(), StatusRange { from: 300, to: 399 });
+        assert_eq!(range(serde_json::json!("5XX")).unwrap(), StatusRange { from: 500, to: 599 });
+    }
+
+    #[test]
+    fn bad_status_ranges_are_rejected() {
+        for value in ["6xx", "299-200", "abc", "99", "200-", "2xxx"] {
+            assert!(range(serde_json::json!(value)).is_err(), "{} was accepted", value);
+        }
+    }
+
+    #[test]
+    fn status_ranges_are_inclusive_and_round_trip() {
+        let ok = range(serde_json::json!("3xx")).unwrap();
+        assert!(ok.contains(300));
+        assert!(ok.contains(399));
+        assert!(!ok.contains(400));
+        assert_eq!(String::from(ok), "300-399");
+        assert_eq!(String::from(StatusRange { from: 204, to: 204 }), "204");
+    }
+}
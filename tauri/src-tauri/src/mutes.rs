@@ -0,0 +1,59 @@
+use chrono::{DateTime, Utc};
+use rusqlite::params;
+use std::collections::HashMap;
+
+use crate::db::Database;
+
+// Longest snooze accepted, so a typo can't silence a monitor for good
+pub const MAX_SNOOZE_DAYS: i64 = 30;
+
+// Notification muting for one monitor, kept in the database so it survives restarts
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MuteState {
+    // muted until the monitor recovers
+    pub acknowledged: bool,
+    pub snoozed_until: Option<DateTime<Utc>>,
+}
+
+impl MuteState {
+    pub fn is_muted(&self, now: DateTime<Utc>) -> bool {
+        self.acknowledged || self.snoozed_until.map(|until| until > now).unwrap_or(false)
+    }
+}
+
+pub fn save(db: &Database, monitor_id: &str, mute: &MuteState) -> Result<(), String> {
+    db.with_conn(|conn| {
+        if *mute == MuteState::default() {
+            conn.execute("DELETE FROM monitor_state WHERE monitor_id = ?1", params![monitor_id])?;
+        } else {
+            conn.execute(
+                "INSERT INTO monitor_state (monitor_id, acknowledged, snoozed_until) VALUES (?1, ?2, ?3)
+                 ON CONFLICT(monitor_id) DO UPDATE SET acknowledged = ?2, snoozed_until = ?3",
+                params![
+                    monitor_id,
+                    mute.acknowledged,
+                    mute.snoozed_until.map(|t| t.timestamp_millis())
+                ],
+            )?;
+        }
+        Ok(())
+    })
+}
+
+pub fn load_all(db: &Database) -> Result<HashMap<String, MuteState>, String> {
+    db.with_conn(|conn| {
+        let mut stmt = conn.prepare("SELECT monitor_id, acknowledged, snoozed_until FROM monitor_state")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                MuteState {
+                    acknowledged: row.get(1)?,
+                    snoozed_until: row
+                        .get::<_, Option<i64>>(2)?
+                        .and_then(DateTime::from_timestamp_millis),
+                },
+            ))
+        })?;
+        rows.collect()
+    })
+}
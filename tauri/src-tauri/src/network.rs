@@ -0,0 +1,174 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::sync::{Mutex, OnceLock};
+use tokio::net::TcpStream;
+use tokio::time::{timeout, Duration, Instant};
+
+// How long a canary verdict is reused, so a cycle full of failures probes once. While no site
+// answers and the canaries keep giving the same verdict, each probe waits twice as long as the
+// one before, up to MAX_VERDICT_TTL; a site answering starts over.
+const VERDICT_TTL: Duration = Duration::from_secs(15);
+const MAX_VERDICT_TTL: Duration = Duration::from_secs(120);
+const CANARY_TIMEOUT: Duration = Duration::from_secs(5);
+const DEFAULT_TCP_PORT: u16 = 443;
+
+// Whether this machine can reach the internet at all, judged by the canary targets
+#[derive(Default)]
+pub struct Network {
+    // one probe at a time; callers that waited reuse its verdict
+    verdict: tokio::sync::Mutex<Option<Verdict>>,
+    offline_since: Mutex<Option<DateTime<Utc>>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Verdict {
+    at: Instant,
+    online: bool,
+    ttl: Duration,
+}
+
+impl Verdict {
+    fn fresh(online: bool) -> Verdict {
+        Verdict {
+            at: Instant::now(),
+            online,
+            ttl: VERDICT_TTL,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone, Copy)]
+pub struct NetworkStatus {
+    pub online: bool,
+    pub offline_since: Option<DateTime<Utc>>,
+}
+
+// What a canary target means: "https://..." is fetched (any HTTP response counts),
+// "host:port" is a TCP connect, and a bare host gets port 443
+enum Canary {
+    Http(url::Url),
+    Tcp(String),
+}
+
+impl Canary {
+    fn parse(target: &str) -> Result<Canary, String> {
+        let target = target.trim();
+        if target.contains("://") {
+            let url = url::Url::parse(target).map_err(|e| format!("invalid canary URL '{}': {}", target, e))?;
+            if !matches!(url.scheme(), "http" | "https") {
+                return Err(format!("canary URL '{}' must use http or https", target));
+            }
+            return Ok(Canary::Http(url));
+        }
+        // "[::1]:53", "1.1.1.1:53", "example.com:80" or "example.com"
+        let has_port = match target.rsplit_once(':') {
+            Some((host, port)) => {
+                port.parse::<u16>().map_err(|_| format!("invalid port in canary '{}'", target))?;
+                if host.is_empty() {
+                    return Err(format!("canary '{}' has no host", target));
+                }
+                true
+            }
+            None => false,
+        };
+        if target.is_empty() || target.contains(char::is_whitespace) || target.contains('/') {
+            return Err(format!("invalid canary '{}'", target));
+        }
+        Ok(Canary::Tcp(if has_port {
+            target.to_string()
+        } else {
+            format!("{}:{}", target, DEFAULT_TCP_PORT)
+        }))
+    }
+
+    async fn reachable(&self) -> bool {
+        match self {
+            Canary::Http(url) => http_client().get(url.clone()).send().await.is_ok(),
+            Canary::Tcp(address) => matches!(timeout(CANARY_TIMEOUT, TcpStream::connect(address)).await, Ok(Ok(_))),
+        }
+    }
+}
+
+pub fn validate_canaries(targets: &[String]) -> Result<(), String> {
+    targets.iter().try_for_each(|target| Canary::parse(target).map(|_| ()))
+}
+
+fn http_client() -> &'static reqwest::Client {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        reqwest::Client::builder()
+            .timeout(CANARY_TIMEOUT)
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .expect("failed to build canary HTTP client")
+    })
+}
+
+impl Network {
+    // True if any canary answers, or if there are none to ask
+    pub async fn probe(&self, targets: &[String]) -> bool {
+        let mut verdict = self.verdict.lock().await;
+        if let Some(cached) = *verdict {
+            if cached.at.elapsed() < cached.ttl {
+                return cached.online;
+            }
+        }
+        let canaries: Vec<Canary> = targets.iter().filter_map(|t| Canary::parse(t).ok()).collect();
+        let online = canaries.is_empty() || {
+            let mut probes = tokio::task::JoinSet::new();
+            for canary in canaries {
+                probes.spawn(async move { canary.reachable().await });
+            }
+            let mut any = false;
+            while let Some(reachable) = probes.join_next().await {
+                if matches!(reachable, Ok(true)) {
+                    any = true;
+                    probes.abort_all();
+                    break;
+                }
+            }
+            any
+        };
+        *verdict = Some(match *verdict {
+            Some(previous) if previous.online == online => Verdict {
+                ttl: (previous.ttl * 2).min(MAX_VERDICT_TTL),
+                ..Verdict::fresh(online)
+            },
+            _ => Verdict::fresh(online),
+        });
+        online
+    }
+
+    // A site answering proves the network is up without asking the canaries
+    pub fn saw_response(&self) {
+        // a probe in progress will come to its own verdict
+        if let Ok(mut verdict) = self.verdict.try_lock() {
+            *verdict = Some(Verdict::fresh(true));
+        }
+    }
+
+    // The next failure asks the canaries again, e.g. after waking up on another network
+    pub fn forget(&self) {
+        if let Ok(mut verdict) = self.verdict.try_lock() {
+            *verdict = None;
+        }
+    }
+
+    pub fn status(&self) -> NetworkStatus {
+        let offline_since = *self.offline_since.lock().unwrap();
+        NetworkStatus {
+            online: offline_since.is_none(),
+            offline_since,
+        }
+    }
+
+    // Returns true if this call changed the state
+    pub fn set_offline(&self, offline: bool, at: DateTime<Utc>) -> bool {
+        let mut offline_since = self.offline_since.lock().unwrap();
+        if offline_since.is_some() == offline {
+            return false;
+        }
+        *offline_since = offline.then_some(at);
+        true
+    }
+}
@@ -0,0 +1,106 @@
+use chrono::{DateTime, Utc};
+use rusqlite::params;
+use serde::Serialize;
+
+use crate::db::Database;
+
+const DEFAULT_PAGE_SIZE: u32 = 50;
+const MAX_PAGE_SIZE: u32 = 500;
+
+// Outcome of sending one event to one channel, after any retries
+#[derive(Debug, Serialize, Clone)]
+pub struct Delivery {
+    pub id: i64,
+    pub channel_id: String,
+    pub event: String,
+    pub monitor_id: Option<String>,
+    pub incident_id: Option<i64>,
+    pub attempts: u32,
+    pub delivered: bool,
+    // status of the last response, if there was one
+    pub status_code: Option<u16>,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub completed_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DeliveryPage {
+    pub total: u64,
+    pub offset: u32,
+    pub limit: u32,
+    pub entries: Vec<Delivery>,
+}
+
+pub fn record(db: &Database, delivery: &Delivery) -> Result<i64, String> {
+    db.with_conn(|conn| {
+        conn.execute(
+            "INSERT INTO deliveries (channel_id, event, monitor_id, incident_id, attempts, delivered, status_code,
+                error, created_at, completed_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                delivery.channel_id,
+                delivery.event,
+                delivery.monitor_id,
+                delivery.incident_id,
+                delivery.attempts,
+                delivery.delivered,
+                delivery.status_code,
+                delivery.error,
+                delivery.created_at.timestamp_millis(),
+                delivery.completed_at.timestamp_millis(),
+            ],
+        )?;
+        Ok(conn.last_insert_rowid())
+    })
+}
+
+// Newest first, optionally for a single channel
+pub fn query(
+    db: &Database,
+    channel_id: Option<&str>,
+    limit: Option<u32>,
+    offset: Option<u32>,
+) -> Result<DeliveryPage, String> {
+    let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    let offset = offset.unwrap_or(0);
+
+    db.with_conn(|conn| {
+        let total: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM deliveries WHERE ?1 IS NULL OR channel_id = ?1",
+            params![channel_id],
+            |row| row.get(0),
+        )?;
+        let mut stmt = conn.prepare(
+            "SELECT id, channel_id, event, monitor_id, incident_id, attempts, delivered, status_code, error,
+                    created_at, completed_at
+             FROM deliveries
+             WHERE ?1 IS NULL OR channel_id = ?1
+             ORDER BY created_at DESC, id DESC
+             LIMIT ?2 OFFSET ?3",
+        )?;
+        let entries = stmt
+            .query_map(params![channel_id, limit, offset], |row| {
+                Ok(Delivery {
+                    id: row.get(0)?,
+                    channel_id: row.get(1)?,
+                    event: row.get(2)?,
+                    monitor_id: row.get(3)?,
+                    incident_id: row.get(4)?,
+                    attempts: row.get(5)?,
+                    delivered: row.get(6)?,
+                    status_code: row.get(7)?,
+                    error: row.get(8)?,
+                    created_at: DateTime::from_timestamp_millis(row.get(9)?).unwrap_or_default(),
+                    completed_at: DateTime::from_timestamp_millis(row.get(10)?).unwrap_or_default(),
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(DeliveryPage {
+            total: total as u64,
+            offset,
+            limit,
+            entries,
+        })
+    })
+}
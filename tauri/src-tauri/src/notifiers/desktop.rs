@@ -0,0 +1,77 @@
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+use tauri_plugin_notification::NotificationExt;
+
+use super::{AlertEvent, AttemptError, Channel, ChannelKind};
+
+// The desktop channel always exists under this id, so rules can name it
+pub const CHANNEL_ID: &str = "desktop";
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct DesktopConfig {}
+
+pub fn channel() -> Channel {
+    Channel {
+        id: CHANNEL_ID.to_string(),
+        name: "Desktop".to_string(),
+        enabled: true,
+        kind: ChannelKind::Desktop(DesktopConfig::default()),
+        has_secret: false,
+    }
+}
+
+// What the desktop announced before it was a channel: going down or degraded, recoveries and
+// certificate problems. Used when no routing rule matches.
+pub fn announces(event: &AlertEvent) -> bool {
+    match event.event.as_str() {
+        "state_changed" => event.is_recovery() || matches!(event.new_state.as_str(), "down" | "degraded"),
+        _ => true,
+    }
+}
+
+pub fn show(app_handle: &AppHandle, event: &AlertEvent) -> Result<(), AttemptError> {
+    let (title, body) = notification(event);
+    app_handle
+        .notification()
+        .builder()
+        .title(title)
+        .body(body)
+        .show()
+        .map_err(|e| AttemptError::permanent(e.to_string()))
+}
+
+fn notification(event: &AlertEvent) -> (String, String) {
+    let url = &event.monitor.url;
+    match (event.event.as_str(), event.new_state.as_str()) {
+        ("certificate_warning", _) => ("Certificate Warning".to_string(), event.error.clone().unwrap_or_default()),
+        ("state_changed", _) if event.is_recovery() => {
+            let duration = event
+                .down_since
+                .map(|since| crate::format_duration((event.occurred_at - since).num_seconds()));
+            let mut body = match duration {
+                Some(duration) => format!("{} is back up after {}", url, duration),
+                None => format!("{} is back up", url),
+            };
+            if let Some(error) = &event.error {
+                body.push_str(&format!(" (last error: {})", error));
+            }
+            ("Website Recovered".to_string(), body)
+        }
+        ("state_changed", "down") => (
+            "Website Down!".to_string(),
+            match &event.error {
+                Some(error) => format!("{} is not responding: {}", url, error),
+                None => format!("{} is not responding", url),
+            },
+        ),
+        ("state_changed", "degraded") => (
+            "Website Degraded".to_string(),
+            format!(
+                "{} is slow: {}",
+                url,
+                event.error.as_deref().unwrap_or("response time over threshold")
+            ),
+        ),
+        _ => (event.title(), event.error.clone().unwrap_or_else(|| url.clone())),
+    }
+}
@@ -0,0 +1,121 @@
+use lettre::message::Mailbox;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use super::{state_colour, AlertEvent};
+
+// Who gets alert emails; shared by the SMTP and Microsoft Graph channels
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct Recipients {
+    // used for monitors without a more specific entry below
+    #[serde(default)]
+    pub to: Vec<String>,
+    // recipients by monitor id, then by group
+    #[serde(default)]
+    pub monitor_recipients: BTreeMap<String, Vec<String>>,
+    #[serde(default)]
+    pub group_recipients: BTreeMap<String, Vec<String>>,
+}
+
+impl Recipients {
+    pub fn validate(&self) -> Result<(), String> {
+        let lists = std::iter::once(&self.to)
+            .chain(self.monitor_recipients.values())
+            .chain(self.group_recipients.values());
+        let mut any = false;
+        for list in lists {
+            for address in list {
+                parse_mailbox(address)?;
+                any = true;
+            }
+        }
+        if !any {
+            return Err("email channel needs at least one recipient".to_string());
+        }
+        Ok(())
+    }
+
+    // The most specific list that applies: the monitor's own, then its group's, then the default
+    pub fn for_event(&self, event: &AlertEvent) -> Result<Vec<Mailbox>, String> {
+        let list = self
+            .monitor_recipients
+            .get(&event.monitor.id)
+            .or_else(|| event.monitor.group.as_ref().and_then(|g| self.group_recipients.get(g)))
+            .unwrap_or(&self.to);
+        if list.is_empty() {
+            return Err(format!("no recipients for {}", event.monitor.url));
+        }
+        list.iter().map(|address| parse_mailbox(address)).collect()
+    }
+}
+
+// Accepts "ops@example.com" or "Ops <ops@example.com>"
+pub fn parse_mailbox(address: &str) -> Result<Mailbox, String> {
+    address
+        .trim()
+        .parse::<Mailbox>()
+        .map_err(|e| format!("invalid email address '{}': {}", address, e))
+}
+
+pub fn subject(event: &AlertEvent) -> String {
+    format!("[Website Monitor] {}", event.title())
+}
+
+pub fn plain_text(event: &AlertEvent) -> String {
+    let mut text = format!("{}\n\n", event.title());
+    for (label, value) in event.facts() {
+        text.push_str(&format!("{}: {}\n", label, value));
+    }
+    text.push_str(&format!("\nOpen site: {}\n", event.monitor.url));
+    if let Some(url) = &event.incident_url {
+        text.push_str(&format!("View incident: {}\n", url));
+    }
+    text
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// Inline styles only; most mail clients ignore <style> blocks
+pub fn html(event: &AlertEvent) -> String {
+    let rows: String = event
+        .facts()
+        .into_iter()
+        .map(|(label, value)| {
+            format!(
+                "<tr><td style=\"padding:4px 12px 4px 0;color:#555;vertical-align:top\">{}</td>\
+                 <td style=\"padding:4px 0\">{}</td></tr>",
+                escape(label),
+                escape(&value)
+            )
+        })
+        .collect();
+    let button = |label: &str, url: &str| {
+        format!(
+            "<a href=\"{}\" style=\"display:inline-block;margin-right:8px;padding:8px 14px;background:#0b5cad;\
+             color:#fff;text-decoration:none;border-radius:4px\">{}</a>",
+            escape(url),
+            escape(label)
+        )
+    };
+    let mut buttons = button("Open site", &event.monitor.url);
+    if let Some(url) = &event.incident_url {
+        buttons.push_str(&button("View incident", url));
+    }
+    format!(
+        "<!DOCTYPE html><html><body style=\"font-family:Segoe UI,Helvetica,Arial,sans-serif;font-size:14px\">\
+         <div style=\"border-left:6px solid {};padding:8px 16px\">\
+         <h2 style=\"margin:0 0 12px 0;font-size:18px\">{}</h2>\
+         <table style=\"border-collapse:collapse\">{}</table>\
+         <p style=\"margin-top:16px\">{}</p>\
+         </div></body></html>",
+        state_colour(&event.new_state),
+        escape(&event.title()),
+        rows,
+        buttons
+    )
+}
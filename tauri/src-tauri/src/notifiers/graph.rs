@@ -0,0 +1,151 @@
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::{AppHandle, Manager};
+
+use super::email::{self, Recipients};
+use super::{http_client, AlertEvent, AttemptError};
+use crate::auth;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GraphMailConfig {
+    #[serde(flatten)]
+    pub recipients: Recipients,
+    // channel (e.g. SMTP) to use instead while nobody is signed in
+    #[serde(default)]
+    pub fallback_channel: Option<String>,
+}
+
+// POST {graph_base_url}/me/sendMail as the signed-in user; needs the Mail.Send scope
+pub async fn send(app_handle: &AppHandle, config: &GraphMailConfig, event: &AlertEvent) -> Result<u16, AttemptError> {
+    let user = auth::signed_in_user(app_handle).ok_or_else(|| {
+        AttemptError::permanent("nobody is signed in; sign in to send email through Microsoft Graph".to_string())
+    })?;
+    let registration = app_handle
+        .state::<crate::AppState>()
+        .app_registration
+        .lock()
+        .unwrap()
+        .clone()
+        .ok_or_else(|| {
+            AttemptError::permanent(
+                "the app registration is unknown; set CLIENT_ID and TENANT_ID in .env or sign in again".to_string(),
+            )
+        })?;
+    let token = auth::ensure_valid_token(
+        app_handle.clone(),
+        &user,
+        &registration.client_id,
+        &registration.tenant_id,
+        60,
+    )
+        .await
+        .map_err(|e| AttemptError {
+            message: format!("failed to get an access token for {}: {}", user, e),
+            status_code: None,
+            retryable: true,
+        })?;
+    post_mail(&auth::graph_base_url(app_handle), &token.access_token, config, event).await
+}
+
+// The sendMail request itself, once there is a token
+async fn post_mail(
+    base_url: &str,
+    access_token: &str,
+    config: &GraphMailConfig,
+    event: &AlertEvent,
+) -> Result<u16, AttemptError> {
+    let recipients: Vec<serde_json::Value> = config
+        .recipients
+        .for_event(event)
+        .map_err(AttemptError::permanent)?
+        .into_iter()
+        .map(|mailbox| {
+            json!({ "emailAddress": { "address": mailbox.email.to_string(), "name": mailbox.name } })
+        })
+        .collect();
+    let body = json!({
+        "message": {
+            "subject": email::subject(event),
+            "body": { "contentType": "HTML", "content": email::html(event) },
+            "toRecipients": recipients,
+        },
+        "saveToSentItems": false,
+    });
+
+    let response = http_client()
+        .post(format!("{}/me/sendMail", base_url))
+        .bearer_auth(access_token)
+        .json(&body)
+        .send()
+        .await
+        .map_err(AttemptError::from_request)?;
+    let status = response.status();
+    if status.is_success() {
+        return Ok(status.as_u16());
+    }
+    let mut error = AttemptError::from_response(status);
+    if status == reqwest::StatusCode::UNAUTHORIZED || status == reqwest::StatusCode::FORBIDDEN {
+        // most likely a sign-in from before Mail.Send was requested
+        error.message = format!("{}; sign in again to grant the Mail.Send permission", error.message);
+    }
+    Err(error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_server::TestServer;
+    use super::*;
+
+    fn config() -> GraphMailConfig {
+        GraphMailConfig {
+            recipients: Recipients {
+                to: vec!["Ops <ops@example.com>".to_string()],
+                ..Recipients::default()
+            },
+            fallback_channel: None,
+        }
+    }
+
+    fn base_url(server: &TestServer) -> String {
+        server.url.trim_end_matches("/hook").to_string()
+    }
+
+    #[tokio::test]
+    async fn mail_is_sent_as_the_signed_in_user() {
+        let server = TestServer::start(vec![202]).await;
+        let event = AlertEvent::test();
+        let status = post_mail(&base_url(&server), "token-1", &config(), &event).await.unwrap();
+        assert_eq!(status, 202);
+
+        let requests = server.requests();
+        let request = &requests[0];
+        assert_eq!(request.path, "/me/sendMail");
+        assert_eq!(request.headers["authorization"], "Bearer token-1");
+        let body = request.json();
+        assert_eq!(body["saveToSentItems"], false);
+        let message = &body["message"];
+        assert_eq!(message["subject"], email::subject(&event));
+        assert_eq!(message["body"]["contentType"], "HTML");
+        assert_eq!(message["body"]["content"], email::html(&event));
+        assert_eq!(
+            message["toRecipients"],
+            json!([{ "emailAddress": { "address": "ops@example.com", "name": "Ops" } }])
+        );
+    }
+
+    #[tokio::test]
+    async fn a_refused_token_asks_for_a_new_sign_in() {
+        let server = TestServer::start(vec![403]).await;
+        let error = post_mail(&base_url(&server), "old", &config(), &AlertEvent::test()).await.unwrap_err();
+        assert!(!error.retryable);
+        assert_eq!(error.status_code, Some(403));
+        assert!(error.message.ends_with("sign in again to grant the Mail.Send permission"));
+    }
+
+    #[tokio::test]
+    async fn throttling_is_retryable() {
+        let server = TestServer::start(vec![429]).await;
+        let error = post_mail(&base_url(&server), "t", &config(), &AlertEvent::test()).await.unwrap_err();
+        assert!(error.retryable);
+    }
+}
@@ -0,0 +1,593 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::sync::OnceLock;
+use tauri::{AppHandle, Manager};
+use tauri_plugin_keyring::KeyringExt;
+use tokio::time::Duration;
+
+use crate::config;
+use crate::monitors;
+
+pub mod deliveries;
+pub mod desktop;
+pub mod email;
+pub mod graph;
+pub mod slack;
+pub mod smtp;
+pub mod teams;
+#[cfg(test)]
+mod test_server;
+pub mod webhook;
+
+const CHANNELS_FILE: &str = "channels.json";
+const CHANNELS_FILE_VERSION: u32 = 1;
+// version of the JSON payload sent to webhooks; bump on breaking changes
+pub const PAYLOAD_VERSION: u32 = 1;
+const MAX_ATTEMPTS: u32 = 4;
+const FIRST_RETRY_DELAY: Duration = Duration::from_secs(2);
+
+// Somewhere alerts are delivered, the desktop included
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Channel {
+    pub id: String,
+    pub name: String,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(flatten)]
+    pub kind: ChannelKind,
+    // whether a secret is stored in the keyring; the secret itself never leaves it
+    #[serde(default)]
+    pub has_secret: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ChannelKind {
+    // the app's own notifications; there is exactly one, see `desktop::CHANNEL_ID`
+    Desktop(desktop::DesktopConfig),
+    Webhook(webhook::WebhookConfig),
+    // Microsoft Teams incoming webhook, sent as an Adaptive Card
+    Teams(teams::TeamsConfig),
+    // Slack incoming webhook, sent as Block Kit
+    Slack(slack::SlackConfig),
+    // email over SMTP; the password is the channel secret
+    Smtp(smtp::SmtpConfig),
+    // email sent through Microsoft Graph as the signed-in user
+    Graph(graph::GraphMailConfig),
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct ChannelInput {
+    pub name: String,
+    pub enabled: Option<bool>,
+    #[serde(flatten)]
+    pub kind: ChannelKind,
+    // None keeps the stored secret, an empty string removes it
+    pub secret: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct ChannelsFile {
+    channels: Vec<Channel>,
+}
+
+impl ChannelKind {
+    fn validate(&self) -> Result<(), String> {
+        match self {
+            ChannelKind::Desktop(_) => Ok(()),
+            ChannelKind::Webhook(config) => config.validate(),
+            ChannelKind::Teams(config) => validate_url(&config.url, "Teams webhook"),
+            ChannelKind::Slack(config) => validate_url(&config.url, "Slack webhook"),
+            ChannelKind::Smtp(config) => config.validate(),
+            ChannelKind::Graph(config) => config.recipients.validate(),
+        }
+    }
+}
+
+impl Channel {
+    pub fn from_input(input: ChannelInput) -> Result<Channel, String> {
+        let mut channel = Channel {
+            id: monitors::new_id(),
+            name: String::new(),
+            enabled: true,
+            kind: input.kind.clone(),
+            has_secret: false,
+        };
+        channel.apply(input)?;
+        Ok(channel)
+    }
+
+    // Secrets are handled separately, see `store_secret`
+    pub fn apply(&mut self, input: ChannelInput) -> Result<(), String> {
+        let name = input.name.trim();
+        if name.is_empty() {
+            return Err("channel needs a name".to_string());
+        }
+        input.kind.validate()?;
+        self.name = name.to_string();
+        self.kind = input.kind;
+        if let Some(enabled) = input.enabled {
+            self.enabled = enabled;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MonitorSummary {
+    pub id: String,
+    pub name: String,
+    pub url: String,
+    pub group: Option<String>,
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Info,
+    Warning,
+    Critical,
+}
+
+impl Severity {
+    // certificate warnings are always `Warning`
+    fn of_state(state: &str) -> Severity {
+        match state {
+            "down" => Severity::Critical,
+            "degraded" => Severity::Warning,
+            _ => Severity::Info,
+        }
+    }
+}
+
+// The alert payload. Webhooks receive it as-is; see the README for the schema.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AlertEvent {
+    pub version: u32,
+    // "state_changed", "certificate_warning" or "test"
+    pub event: String,
+    pub monitor: MonitorSummary,
+    pub old_state: String,
+    pub new_state: String,
+    pub severity: Severity,
+    pub error: Option<String>,
+    // response time of the check that caused the change, if it got a response
+    pub latency_ms: Option<u64>,
+    pub incident_id: Option<i64>,
+    // link built from the incident_url_template setting
+    pub incident_url: Option<String>,
+    pub occurred_at: DateTime<Utc>,
+    pub down_since: Option<DateTime<Utc>>,
+}
+
+impl AlertEvent {
+    pub fn state_changed(
+        monitor: &monitors::Monitor,
+        old_state: &str,
+        new_state: &str,
+        error: Option<String>,
+        latency_ms: Option<u64>,
+        incident_id: Option<i64>,
+        down_since: Option<DateTime<Utc>>,
+    ) -> AlertEvent {
+        AlertEvent {
+            version: PAYLOAD_VERSION,
+            event: "state_changed".to_string(),
+            monitor: MonitorSummary {
+                id: monitor.id.clone(),
+                name: monitor.name.clone(),
+                url: monitor.url.clone(),
+                group: monitor.group.clone(),
+                tags: monitor.tags.clone(),
+            },
+            old_state: old_state.to_string(),
+            new_state: new_state.to_string(),
+            severity: Severity::of_state(new_state),
+            error,
+            latency_ms,
+            incident_id,
+            incident_url: None,
+            occurred_at: Utc::now(),
+            down_since,
+        }
+    }
+
+    // A certificate problem; the state doesn't change, so old and new are the current state
+    pub fn certificate_warning(monitor: &monitors::Monitor, state: &str, description: String) -> AlertEvent {
+        AlertEvent {
+            event: "certificate_warning".to_string(),
+            severity: Severity::Warning,
+            ..AlertEvent::state_changed(monitor, state, state, Some(description), None, None, None)
+        }
+    }
+
+    // Back to up or degraded from an outage, which may have run through a maintenance window
+    pub fn is_recovery(&self) -> bool {
+        self.event == "state_changed"
+            && matches!(self.new_state.as_str(), "up" | "degraded")
+            && self.down_since.is_some()
+    }
+
+    pub fn title(&self) -> String {
+        let name = if self.monitor.name.is_empty() { &self.monitor.url } else { &self.monitor.name };
+        match (self.event.as_str(), self.new_state.as_str()) {
+            ("test", _) => "Test alert from Website Monitor".to_string(),
+            ("certificate_warning", _) => format!("Certificate warning for {}", name),
+            (_, "up") if self.old_state == "down" => format!("{} has recovered", name),
+            (_, "up") => format!("{} is up", name),
+            (_, "degraded") => format!("{} is degraded", name),
+            (_, "down") => format!("{} is down", name),
+            (_, "maintenance") => format!("{} is in maintenance", name),
+            (_, state) => format!("{} is {}", name, state),
+        }
+    }
+
+    // Label/value pairs shown in chat messages
+    pub fn facts(&self) -> Vec<(&'static str, String)> {
+        let state = if self.old_state == self.new_state {
+            self.new_state.clone()
+        } else {
+            format!("{} → {}", self.old_state, self.new_state)
+        };
+        let mut facts = vec![("URL", self.monitor.url.clone()), ("State", state)];
+        if let Some(group) = &self.monitor.group {
+            facts.push(("Group", group.clone()));
+        }
+        if let Some(error) = &self.error {
+            let label = if self.event == "certificate_warning" { "Certificate" } else { "Reason" };
+            facts.push((label, error.clone()));
+        }
+        if let Some(latency) = self.latency_ms {
+            facts.push(("Latency", format!("{} ms", latency)));
+        }
+        if let Some(down_since) = self.down_since.filter(|_| self.old_state == "down" || self.new_state == "down") {
+            facts.push(("Down since", down_since.to_rfc3339_opts(chrono::SecondsFormat::Secs, true)));
+        }
+        if let Some(incident_id) = self.incident_id {
+            facts.push(("Incident", format!("#{}", incident_id)));
+        }
+        facts
+    }
+
+    pub fn test() -> AlertEvent {
+        AlertEvent {
+            version: PAYLOAD_VERSION,
+            event: "test".to_string(),
+            monitor: MonitorSummary {
+                id: "test".to_string(),
+                name: "Test monitor".to_string(),
+                url: "https://example.com".to_string(),
+                group: None,
+                tags: Vec::new(),
+            },
+            old_state: "up".to_string(),
+            new_state: "down".to_string(),
+            severity: Severity::Critical,
+            error: Some("This is a test alert".to_string()),
+            latency_ms: Some(1234),
+            incident_id: None,
+            incident_url: None,
+            occurred_at: Utc::now(),
+            down_since: Some(Utc::now()),
+        }
+    }
+}
+
+// Why a single attempt failed, and whether trying again could help
+#[derive(Debug)]
+pub struct AttemptError {
+    pub message: String,
+    pub status_code: Option<u16>,
+    pub retryable: bool,
+}
+
+impl AttemptError {
+    pub fn permanent(message: String) -> AttemptError {
+        AttemptError {
+            message,
+            status_code: None,
+            retryable: false,
+        }
+    }
+
+    pub fn from_response(status: reqwest::StatusCode) -> AttemptError {
+        AttemptError {
+            message: format!("receiver returned {}", status),
+            status_code: Some(status.as_u16()),
+            // other 4xx mean the request itself is wrong, so sending it again won't help
+            retryable: status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS,
+        }
+    }
+
+    pub fn from_request(error: reqwest::Error) -> AttemptError {
+        AttemptError {
+            message: error.to_string(),
+            status_code: None,
+            retryable: true,
+        }
+    }
+}
+
+// Status colour used by Slack attachments and email
+pub fn state_colour(state: &str) -> &'static str {
+    match state {
+        "up" => "#2eb886",
+        "degraded" => "#daa038",
+        "down" => "#a30200",
+        _ => "#439fe0",
+    }
+}
+
+fn validate_url(value: &str, what: &str) -> Result<(), String> {
+    let url = url::Url::parse(value.trim()).map_err(|e| format!("invalid {} URL: {}", what, e))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(format!("{} URL must use http or https", what));
+    }
+    Ok(())
+}
+
+// POSTs a JSON body; any 2xx response counts as delivered
+pub async fn post_json(url: &str, body: &serde_json::Value) -> Result<u16, AttemptError> {
+    let response = http_client()
+        .post(url.trim())
+        .json(body)
+        .send()
+        .await
+        .map_err(AttemptError::from_request)?;
+    let status = response.status();
+    if status.is_success() {
+        Ok(status.as_u16())
+    } else {
+        Err(AttemptError::from_response(status))
+    }
+}
+
+pub fn http_client() -> &'static reqwest::Client {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        reqwest::Client::builder()
+            .timeout(std::time::Duration::from_secs(10))
+            .build()
+            .expect("failed to build notifier HTTP client")
+    })
+}
+
+fn secret_key(app_handle: &AppHandle, channel_id: &str) -> (String, String) {
+    let service = app_handle.package_info().name.to_string();
+    let key = format!("{}::channel::{}", service, channel_id);
+    (service, key)
+}
+
+pub fn load_secret(app_handle: &AppHandle, channel_id: &str) -> Option<String> {
+    let (service, key) = secret_key(app_handle, channel_id);
+    app_handle.keyring().get_password(&service, &key).ok().flatten()
+}
+
+// Some(secret) stores it, None deletes it
+pub fn store_secret(app_handle: &AppHandle, channel_id: &str, secret: Option<&str>) -> Result<(), String> {
+    let (service, key) = secret_key(app_handle, channel_id);
+    match secret {
+        Some(secret) => app_handle.keyring().set_password(&service, &key, secret).map_err(|e| e.to_string()),
+        // nothing stored is fine
+        None if load_secret(app_handle, channel_id).is_none() => Ok(()),
+        None => app_handle.keyring().delete_password(&service, &key).map_err(|e| e.to_string()),
+    }
+}
+
+// Sends the event in the background to every enabled channel the monitor routes to
+// (all of them when `routes` is None)
+pub fn dispatch(app_handle: &AppHandle, mut event: AlertEvent, routes: Option<&[String]>) {
+    let state = app_handle.state::<crate::AppState>();
+    if let Some(incident_id) = event.incident_id {
+        event.incident_url = state.settings.lock().unwrap().incident_url(incident_id);
+    }
+    let channels: Vec<Channel> = state
+        .channels
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|c| c.enabled && routes.is_none_or(|routes| routes.contains(&c.id)))
+        .cloned()
+        .collect();
+    for channel in channels {
+        let app_handle = app_handle.clone();
+        let event = event.clone();
+        tauri::async_runtime::spawn(async move {
+            deliver(&app_handle, &channel, &event).await;
+        });
+    }
+}
+
+// Delivers one event to one channel, retrying with exponential backoff, and logs the outcome
+pub async fn deliver(app_handle: &AppHandle, channel: &Channel, event: &AlertEvent) -> deliveries::Delivery {
+    let fallback = graph_fallback(app_handle, channel);
+    let channel = fallback.as_ref().unwrap_or(channel);
+    let secret = if channel.has_secret {
+        load_secret(app_handle, &channel.id)
+    } else {
+        None
+    };
+    let secret = secret.as_deref();
+    let created_at = Utc::now();
+    let (outcome, attempts) = with_retries(&channel.name, FIRST_RETRY_DELAY, || async move {
+        // the desktop has no status code to report
+        match &channel.kind {
+            ChannelKind::Desktop(_) => desktop::show(app_handle, event).map(|()| None),
+            ChannelKind::Webhook(config) => webhook::send(config, secret, event).await.map(Some),
+            ChannelKind::Teams(config) => post_json(&config.url, &teams::card(event)).await.map(Some),
+            ChannelKind::Slack(config) => post_json(&config.url, &slack::message(event)).await.map(Some),
+            ChannelKind::Smtp(config) => smtp::send(config, secret, event).await.map(Some),
+            ChannelKind::Graph(config) => graph::send(app_handle, config, event).await.map(Some),
+        }
+    })
+    .await;
+
+    let delivery = deliveries::Delivery {
+        id: 0,
+        channel_id: channel.id.clone(),
+        event: event.event.clone(),
+        monitor_id: Some(event.monitor.id.clone()).filter(|_| event.event != "test"),
+        incident_id: event.incident_id,
+        attempts,
+        delivered: outcome.is_ok(),
+        status_code: match &outcome {
+            Ok(status) => *status,
+            Err(e) => e.status_code,
+        },
+        error: outcome.as_ref().err().map(|e| e.message.clone()),
+        created_at,
+        completed_at: Utc::now(),
+    };
+    if let Some(error) = &delivery.error {
+        eprintln!("Failed to deliver {} alert to {}: {}", event.event, channel.name, error);
+    }
+    let state = app_handle.state::<crate::AppState>();
+    if let Some(db) = &state.db {
+        match deliveries::record(db, &delivery) {
+            Ok(id) => return deliveries::Delivery { id, ..delivery },
+            Err(e) => eprintln!("Failed to record delivery: {}", e),
+        }
+    }
+    delivery
+}
+
+// Makes up to MAX_ATTEMPTS attempts while they fail in a retryable way, waiting `first_delay`
+// after the first and twice as long after each one after that. Returns the last outcome and
+// how many attempts it took.
+async fn with_retries<T, F, Fut>(
+    channel_name: &str,
+    first_delay: Duration,
+    mut attempt: F,
+) -> (Result<T, AttemptError>, u32)
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, AttemptError>>,
+{
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        match attempt().await {
+            Err(e) if e.retryable && attempts < MAX_ATTEMPTS => {
+                let delay = first_delay * 2u32.pow(attempts - 1);
+                eprintln!(
+                    "Delivery to {} failed (attempt {}): {}; retrying in {:?}",
+                    channel_name, attempts, e.message, delay
+                );
+                tokio::time::sleep(delay).await;
+            }
+            result => return (result, attempts),
+        }
+    }
+}
+
+// With nobody signed in, a Graph channel hands its alerts to its fallback channel
+fn graph_fallback(app_handle: &AppHandle, channel: &Channel) -> Option<Channel> {
+    let ChannelKind::Graph(config) = &channel.kind else {
+        return None;
+    };
+    let fallback_id = config.fallback_channel.as_ref()?;
+    if crate::auth::signed_in_user(app_handle).is_some() {
+        return None;
+    }
+    let state = app_handle.state::<crate::AppState>();
+    let fallback = state
+        .channels
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|c| &c.id == fallback_id && c.enabled && !matches!(c.kind, ChannelKind::Graph(_)))
+        .cloned()?;
+    Some(fallback)
+}
+
+// The desktop channel is added if the file doesn't have it yet
+pub fn load(app_handle: &AppHandle) -> Result<Vec<Channel>, String> {
+    let path = config::config_path(app_handle, CHANNELS_FILE)?;
+    let file: Option<ChannelsFile> = config::load_versioned(&path, CHANNELS_FILE_VERSION)?;
+    let mut channels = file.unwrap_or_default().channels;
+    if !channels.iter().any(|c| c.id == desktop::CHANNEL_ID) {
+        channels.insert(0, desktop::channel());
+    }
+    Ok(channels)
+}
+
+pub fn save(app_handle: &AppHandle, channels: &[Channel]) -> Result<(), String> {
+    let path = config::config_path(app_handle, CHANNELS_FILE)?;
+    let file = ChannelsFile {
+        channels: channels.to_vec(),
+    };
+    config::save_versioned(&path, CHANNELS_FILE_VERSION, &file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::test_server::TestServer;
+    use super::*;
+    use tokio::time::Instant;
+
+    const DELAY: Duration = Duration::from_millis(20);
+
+    async fn post_with_retries(url: &str) -> (Result<u16, AttemptError>, u32) {
+        let body = serde_json::json!({ "ok": true });
+        with_retries("test", DELAY, || post_json(url, &body)).await
+    }
+
+    #[tokio::test]
+    async fn server_errors_are_retried_with_backoff() {
+        let server = TestServer::start(vec![503, 500, 200]).await;
+        let started = Instant::now();
+        let (outcome, attempts) = post_with_retries(&server.url).await;
+        assert_eq!(outcome.unwrap(), 200);
+        assert_eq!(attempts, 3);
+        // waited DELAY, then twice that
+        assert!(started.elapsed() >= DELAY * 3);
+        assert_eq!(server.requests().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn retries_stop_after_max_attempts() {
+        let server = TestServer::start(vec![429]).await;
+        let (outcome, attempts) = post_with_retries(&server.url).await;
+        let error = outcome.unwrap_err();
+        assert_eq!(attempts, MAX_ATTEMPTS);
+        assert_eq!(error.status_code, Some(429));
+        assert_eq!(server.requests().len(), MAX_ATTEMPTS as usize);
+    }
+
+    #[tokio::test]
+    async fn client_errors_are_not_retried() {
+        let server = TestServer::start(vec![404, 200]).await;
+        let (outcome, attempts) = post_with_retries(&server.url).await;
+        let error = outcome.unwrap_err();
+        assert_eq!(attempts, 1);
+        assert_eq!(error.status_code, Some(404));
+        assert!(!error.retryable);
+    }
+
+    #[tokio::test]
+    async fn connection_errors_are_retried() {
+        // bind and drop a listener to get a port nothing listens on
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let (outcome, attempts) = post_with_retries(&format!("http://127.0.0.1:{}/hook", port)).await;
+        assert!(outcome.unwrap_err().retryable);
+        assert_eq!(attempts, MAX_ATTEMPTS);
+    }
+
+    #[test]
+    fn recoveries_need_an_outage() {
+        let mut event = AlertEvent::test();
+        event.event = "state_changed".to_string();
+        event.new_state = "up".to_string();
+        assert!(event.is_recovery());
+        event.new_state = "degraded".to_string();
+        assert!(event.is_recovery());
+        event.down_since = None;
+        assert!(!event.is_recovery());
+    }
+}
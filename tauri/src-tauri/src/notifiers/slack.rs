@@ -0,0 +1,107 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use super::{state_colour, AlertEvent};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SlackConfig {
+    pub url: String,
+}
+
+// Slack treats &, < and > as markup in mrkdwn text
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+// An incoming-webhook message: Block Kit blocks inside a coloured attachment, plus plain text
+// for notifications and clients that can't show blocks
+pub fn message(event: &AlertEvent) -> Value {
+    let title = event.title();
+    let fields: Vec<Value> = event
+        .facts()
+        .into_iter()
+        .map(|(label, value)| json!({ "type": "mrkdwn", "text": format!("*{}*\n{}", label, escape(&value)) }))
+        .collect();
+    let mut buttons = vec![json!({
+        "type": "button",
+        "action_id": "open_site",
+        "text": { "type": "plain_text", "text": "Open site" },
+        "url": event.monitor.url,
+    })];
+    if let Some(url) = &event.incident_url {
+        buttons.push(json!({
+            "type": "button",
+            "action_id": "view_incident",
+            "text": { "type": "plain_text", "text": "View incident" },
+            "url": url,
+        }));
+    }
+    // a section takes at most 10 fields
+    let sections: Vec<Value> = fields
+        .chunks(10)
+        .map(|chunk| json!({ "type": "section", "fields": chunk }))
+        .collect();
+    let mut blocks = vec![json!({
+        "type": "section",
+        "text": { "type": "mrkdwn", "text": format!("*{}*", escape(&title)) },
+    })];
+    blocks.extend(sections);
+    blocks.push(json!({ "type": "actions", "elements": buttons }));
+    json!({
+        "text": title,
+        "attachments": [{ "color": state_colour(&event.new_state), "blocks": blocks }],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_server::TestServer;
+    use super::*;
+
+    fn degraded_event() -> AlertEvent {
+        let mut event = AlertEvent::test();
+        event.event = "state_changed".to_string();
+        event.new_state = "degraded".to_string();
+        event.error = Some("response time 900 ms is over <the> warning threshold & more".to_string());
+        event
+    }
+
+    #[test]
+    fn messages_have_text_colour_and_blocks() {
+        let message = message(&degraded_event());
+        assert_eq!(message["text"], "Test monitor is degraded");
+        let attachment = &message["attachments"][0];
+        assert_eq!(attachment["color"], state_colour("degraded"));
+        let blocks = attachment["blocks"].as_array().unwrap();
+        assert_eq!(blocks[0]["text"]["text"], "*Test monitor is degraded*");
+        assert_eq!(blocks[1]["type"], "section");
+        let fields = blocks[1]["fields"].as_array().unwrap();
+        assert!(fields.contains(&json!({
+            "type": "mrkdwn",
+            "text": "*Reason*\nresponse time 900 ms is over &lt;the&gt; warning threshold &amp; more",
+        })));
+        let actions = blocks.last().unwrap();
+        assert_eq!(actions["type"], "actions");
+        assert_eq!(actions["elements"].as_array().unwrap().len(), 1);
+        assert_eq!(actions["elements"][0]["url"], "https://example.com");
+    }
+
+    #[test]
+    fn incidents_get_a_button() {
+        let mut event = degraded_event();
+        event.incident_url = Some("https://status.example.com/incidents/7".to_string());
+        let message = message(&event);
+        let blocks = message["attachments"][0]["blocks"].as_array().unwrap();
+        let buttons = blocks.last().unwrap()["elements"].as_array().unwrap();
+        assert_eq!(buttons[1]["action_id"], "view_incident");
+        assert_eq!(buttons[1]["url"], "https://status.example.com/incidents/7");
+    }
+
+    #[tokio::test]
+    async fn messages_are_posted_as_json() {
+        let server = TestServer::start(vec![200]).await;
+        let message = message(&degraded_event());
+        super::super::post_json(&server.url, &message).await.unwrap();
+        assert_eq!(server.requests()[0].json(), message);
+    }
+}
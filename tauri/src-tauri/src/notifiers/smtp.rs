@@ -0,0 +1,165 @@
+use lettre::message::{header::ContentType, MultiPart, SinglePart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use serde::{Deserialize, Serialize};
+
+use super::email::{self, Recipients};
+use super::{AlertEvent, AttemptError};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SmtpSecurity {
+    // plain connection upgraded with STARTTLS, usually port 587
+    #[default]
+    StartTls,
+    // TLS from the first byte, usually port 465
+    Tls,
+    // no encryption at all; only for local test sinks
+    None,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SmtpConfig {
+    pub host: String,
+    // defaults to the usual port for the security mode
+    #[serde(default)]
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub security: SmtpSecurity,
+    // the password is the channel secret, kept in the keyring
+    #[serde(default)]
+    pub username: Option<String>,
+    pub from: String,
+    #[serde(flatten)]
+    pub recipients: Recipients,
+}
+
+impl SmtpConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.host.trim().is_empty() {
+            return Err("SMTP host is required".to_string());
+        }
+        email::parse_mailbox(&self.from)?;
+        self.recipients.validate()
+    }
+}
+
+pub async fn send(config: &SmtpConfig, password: Option<&str>, event: &AlertEvent) -> Result<u16, AttemptError> {
+    let mut builder = Message::builder()
+        .from(email::parse_mailbox(&config.from).map_err(AttemptError::permanent)?)
+        .subject(email::subject(event));
+    for mailbox in config.recipients.for_event(event).map_err(AttemptError::permanent)? {
+        builder = builder.to(mailbox);
+    }
+    let message = builder
+        .multipart(
+            MultiPart::alternative()
+                .singlepart(SinglePart::builder().header(ContentType::TEXT_PLAIN).body(email::plain_text(event)))
+                .singlepart(SinglePart::builder().header(ContentType::TEXT_HTML).body(email::html(event))),
+        )
+        .map_err(|e| AttemptError::permanent(e.to_string()))?;
+
+    let host = config.host.trim();
+    let transport = match config.security {
+        SmtpSecurity::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host),
+        SmtpSecurity::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(host),
+        SmtpSecurity::None => Ok(AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host)),
+    }
+    .map_err(|e| AttemptError::permanent(e.to_string()))?;
+    let mut transport = transport.timeout(Some(std::time::Duration::from_secs(10)));
+    if let Some(port) = config.port {
+        transport = transport.port(port);
+    }
+    if let Some(username) = config.username.as_deref().filter(|u| !u.trim().is_empty()) {
+        transport = transport.credentials(Credentials::new(
+            username.trim().to_string(),
+            password.unwrap_or_default().to_string(),
+        ));
+    }
+
+    match transport.build().send(message).await {
+        Ok(response) => Ok(response.code().into()),
+        Err(e) => Err(AttemptError {
+            message: e.to_string(),
+            status_code: e.status().map(u16::from),
+            // 5xx replies (bad recipient, auth refused...) won't improve on a retry
+            retryable: !e.is_permanent(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_server::SmtpSink;
+    use super::*;
+
+    fn config(port: u16) -> SmtpConfig {
+        SmtpConfig {
+            host: "127.0.0.1".to_string(),
+            port: Some(port),
+            security: SmtpSecurity::None,
+            username: None,
+            from: "Monitor <monitor@example.com>".to_string(),
+            recipients: Recipients {
+                to: vec!["ops@example.com".to_string()],
+                group_recipients: [("web".to_string(), vec!["web@example.com".to_string()])].into(),
+                ..Recipients::default()
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn alerts_are_sent_as_plain_text_and_html() {
+        let sink = SmtpSink::start("250 OK").await;
+        let status = send(&config(sink.port), None, &AlertEvent::test()).await.unwrap();
+        assert_eq!(status, 250);
+
+        let messages = sink.messages();
+        let mail = &messages[0];
+        assert_eq!(mail.from, "<monitor@example.com>");
+        assert_eq!(mail.to, vec!["<ops@example.com>".to_string()]);
+        assert!(mail.data.contains("Subject: [Website Monitor] Test alert from Website Monitor\r\n"));
+        assert!(mail.data.contains("multipart/alternative"));
+        assert!(mail.data.contains("Content-Type: text/plain; charset=utf-8"));
+        assert!(mail.data.contains("Content-Type: text/html; charset=utf-8"));
+    }
+
+    #[tokio::test]
+    async fn group_recipients_win_over_the_default_list() {
+        let sink = SmtpSink::start("250 OK").await;
+        let mut event = AlertEvent::test();
+        event.monitor.group = Some("web".to_string());
+        send(&config(sink.port), None, &event).await.unwrap();
+        assert_eq!(sink.messages()[0].to, vec!["<web@example.com>".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn refused_recipients_are_not_retried() {
+        let sink = SmtpSink::start("550 no such user").await;
+        let error = send(&config(sink.port), None, &AlertEvent::test()).await.unwrap_err();
+        assert!(!error.retryable);
+        assert_eq!(error.status_code, Some(550));
+        assert!(sink.messages().is_empty());
+    }
+
+    #[tokio::test]
+    async fn an_unreachable_server_is_retried() {
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let error = send(&config(port), None, &AlertEvent::test()).await.unwrap_err();
+        assert!(error.retryable);
+    }
+
+    #[test]
+    fn configs_need_a_host_a_sender_and_recipients() {
+        assert!(config(25).validate().is_ok());
+        let mut no_host = config(25);
+        no_host.host = " ".to_string();
+        assert!(no_host.validate().is_err());
+        let mut bad_from = config(25);
+        bad_from.from = "not an address".to_string();
+        assert!(bad_from.validate().is_err());
+        let mut nobody = config(25);
+        nobody.recipients = Recipients::default();
+        assert_eq!(nobody.validate(), Err("email channel needs at least one recipient".to_string()));
+    }
+}
@@ -0,0 +1,114 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use super::AlertEvent;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TeamsConfig {
+    pub url: String,
+}
+
+// Adaptive Card container styles double as the status colour
+fn style(state: &str) -> &'static str {
+    match state {
+        "up" => "good",
+        "degraded" => "warning",
+        "down" => "attention",
+        _ => "emphasis",
+    }
+}
+
+// An incoming-webhook message carrying one Adaptive Card
+pub fn card(event: &AlertEvent) -> Value {
+    let facts: Vec<Value> = event
+        .facts()
+        .into_iter()
+        .map(|(title, value)| json!({ "title": title, "value": value }))
+        .collect();
+    let mut actions = vec![json!({
+        "type": "Action.OpenUrl",
+        "title": "Open site",
+        "url": event.monitor.url,
+    })];
+    if let Some(url) = &event.incident_url {
+        actions.push(json!({ "type": "Action.OpenUrl", "title": "View incident", "url": url }));
+    }
+    json!({
+        "type": "message",
+        "attachments": [{
+            "contentType": "application/vnd.microsoft.card.adaptive",
+            "contentUrl": null,
+            "content": {
+                "$schema": "http://adaptivecards.io/schemas/adaptive-card.json",
+                "type": "AdaptiveCard",
+                "version": "1.4",
+                "msteams": { "width": "Full" },
+                "body": [
+                    {
+                        "type": "Container",
+                        "style": style(&event.new_state),
+                        "bleed": true,
+                        "items": [{
+                            "type": "TextBlock",
+                            "text": event.title(),
+                            "weight": "Bolder",
+                            "size": "Medium",
+                            "wrap": true,
+                        }],
+                    },
+                    { "type": "FactSet", "facts": facts },
+                ],
+                "actions": actions,
+            },
+        }],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_server::TestServer;
+    use super::*;
+
+    fn down_event() -> AlertEvent {
+        let mut event = AlertEvent::test();
+        event.event = "state_changed".to_string();
+        event.incident_id = Some(42);
+        event.incident_url = Some("https://status.example.com/incidents/42".to_string());
+        event
+    }
+
+    #[test]
+    fn cards_show_state_facts_and_links() {
+        let message = card(&down_event());
+        assert_eq!(message["type"], "message");
+        let attachment = &message["attachments"][0];
+        assert_eq!(attachment["contentType"], "application/vnd.microsoft.card.adaptive");
+        let content = &attachment["content"];
+        assert_eq!(content["type"], "AdaptiveCard");
+        assert_eq!(content["body"][0]["style"], "attention");
+        assert_eq!(content["body"][0]["items"][0]["text"], "Test monitor is down");
+        let facts = content["body"][1]["facts"].as_array().unwrap();
+        assert!(facts.contains(&json!({ "title": "State", "value": "up → down" })));
+        assert!(facts.contains(&json!({ "title": "Incident", "value": "#42" })));
+        let actions = content["actions"].as_array().unwrap();
+        assert_eq!(actions.len(), 2);
+        assert_eq!(actions[0]["url"], "https://example.com");
+        assert_eq!(actions[1]["url"], "https://status.example.com/incidents/42");
+    }
+
+    #[test]
+    fn cards_leave_out_the_incident_link_without_one() {
+        let message = card(&AlertEvent::test());
+        assert_eq!(message["attachments"][0]["content"]["actions"].as_array().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn cards_are_posted_as_json() {
+        let server = TestServer::start(vec![202]).await;
+        let message = card(&down_event());
+        assert_eq!(super::super::post_json(&server.url, &message).await.unwrap(), 202);
+        let requests = server.requests();
+        assert_eq!(requests[0].headers["content-type"], "application/json");
+        assert_eq!(requests[0].json(), message);
+    }
+}
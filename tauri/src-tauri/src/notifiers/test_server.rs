@@ -0,0 +1,139 @@
+// Local stand-ins for the services notifiers talk to, for tests
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+
+pub struct Request {
+    pub path: String,
+    // names are lower case
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    pub fn json(&self) -> serde_json::Value {
+        serde_json::from_slice(&self.body).unwrap()
+    }
+}
+
+// An HTTP receiver that records each request and answers with the next status from a list
+// (the last one repeats)
+pub struct TestServer {
+    pub url: String,
+    requests: Arc<Mutex<Vec<Request>>>,
+}
+
+impl TestServer {
+    pub async fn start(statuses: Vec<u16>) -> TestServer {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = requests.clone();
+        tokio::spawn(async move {
+            for served in 0.. {
+                let Ok((stream, _)) = listener.accept().await else {
+                    return;
+                };
+                let mut stream = BufReader::new(stream);
+                let mut line = String::new();
+                stream.read_line(&mut line).await.unwrap();
+                let path = line.split_whitespace().nth(1).unwrap_or_default().to_string();
+                let mut headers = HashMap::new();
+                loop {
+                    line.clear();
+                    stream.read_line(&mut line).await.unwrap();
+                    let Some((name, value)) = line.trim_end().split_once(':') else {
+                        break;
+                    };
+                    headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+                }
+                let length = headers.get("content-length").and_then(|l| l.parse().ok()).unwrap_or(0);
+                let mut body = vec![0; length];
+                stream.read_exact(&mut body).await.unwrap();
+                recorded.lock().unwrap().push(Request { path, headers, body });
+
+                let status = statuses.get(served).or(statuses.last()).copied().unwrap_or(200);
+                let response = format!(
+                    "HTTP/1.1 {} Test\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+                    status
+                );
+                let _ = stream.get_mut().write_all(response.as_bytes()).await;
+            }
+        });
+        TestServer { url, requests }
+    }
+
+    pub fn requests(&self) -> std::sync::MutexGuard<'_, Vec<Request>> {
+        self.requests.lock().unwrap()
+    }
+}
+
+// An SMTP server that accepts every message, or refuses recipients with `rcpt_reply`
+pub struct SmtpSink {
+    pub port: u16,
+    messages: Arc<Mutex<Vec<Mail>>>,
+}
+
+#[derive(Default)]
+pub struct Mail {
+    pub from: String,
+    pub to: Vec<String>,
+    pub data: String,
+}
+
+impl SmtpSink {
+    pub async fn start(rcpt_reply: &'static str) -> SmtpSink {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let messages = Arc::new(Mutex::new(Vec::new()));
+        let recorded = messages.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let (reader, mut writer) = stream.into_split();
+                let mut reader = BufReader::new(reader);
+                let mut mail = Mail::default();
+                writer.write_all(b"220 sink ESMTP\r\n").await.unwrap();
+                let mut line = String::new();
+                while reader.read_line(&mut line).await.unwrap_or(0) > 0 {
+                    let command = line.trim_end().to_string();
+                    line.clear();
+                    let upper = command.to_uppercase();
+                    let reply = if upper.starts_with("EHLO") || upper.starts_with("HELO") {
+                        "250 sink"
+                    } else if let Some(from) = upper.strip_prefix("MAIL FROM:") {
+                        mail.from = command[command.len() - from.len()..].to_string();
+                        "250 OK"
+                    } else if let Some(to) = upper.strip_prefix("RCPT TO:") {
+                        mail.to.push(command[command.len() - to.len()..].to_string());
+                        rcpt_reply
+                    } else if upper == "DATA" {
+                        writer.write_all(b"354 go ahead\r\n").await.unwrap();
+                        loop {
+                            reader.read_line(&mut line).await.unwrap();
+                            if line == ".\r\n" {
+                                break;
+                            }
+                            mail.data.push_str(&line);
+                            line.clear();
+                        }
+                        line.clear();
+                        recorded.lock().unwrap().push(std::mem::take(&mut mail));
+                        "250 queued"
+                    } else if upper == "QUIT" {
+                        let _ = writer.write_all(b"221 bye\r\n").await;
+                        break;
+                    } else {
+                        "250 OK"
+                    };
+                    writer.write_all(format!("{}\r\n", reply).as_bytes()).await.unwrap();
+                }
+            }
+        });
+        SmtpSink { port, messages }
+    }
+
+    pub fn messages(&self) -> std::sync::MutexGuard<'_, Vec<Mail>> {
+        self.messages.lock().unwrap()
+    }
+}
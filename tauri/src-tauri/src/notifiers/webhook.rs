@@ -0,0 +1,126 @@
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::BTreeMap;
+
+use super::{http_client, AlertEvent, AttemptError};
+
+pub const SIGNATURE_HEADER: &str = "X-Monitor-Signature";
+pub const EVENT_HEADER: &str = "X-Monitor-Event";
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WebhookConfig {
+    pub url: String,
+    // extra headers sent with every request, e.g. an API key the receiver expects
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+}
+
+impl WebhookConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        let url = url::Url::parse(self.url.trim()).map_err(|e| format!("invalid webhook URL: {}", e))?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err("webhook URL must use http or https".to_string());
+        }
+        for (name, value) in &self.headers {
+            reqwest::header::HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| format!("invalid header name '{}'", name))?;
+            reqwest::header::HeaderValue::from_str(value)
+                .map_err(|_| format!("invalid value for header '{}'", name))?;
+        }
+        Ok(())
+    }
+}
+
+// "sha256=<hex HMAC-SHA256 of the raw body>"
+pub fn signature(secret: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(body);
+    let digest = mac.finalize().into_bytes();
+    format!("sha256={}", digest.iter().map(|b| format!("{:02x}", b)).collect::<String>())
+}
+
+pub async fn send(config: &WebhookConfig, secret: Option<&str>, event: &AlertEvent) -> Result<u16, AttemptError> {
+    let body = serde_json::to_vec(event).map_err(|e| AttemptError {
+        message: e.to_string(),
+        status_code: None,
+        retryable: false,
+    })?;
+    let mut request = http_client()
+        .post(config.url.trim())
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(EVENT_HEADER, &event.event);
+    for (name, value) in &config.headers {
+        request = request.header(name, value);
+    }
+    if let Some(secret) = secret {
+        request = request.header(SIGNATURE_HEADER, signature(secret, &body));
+    }
+    let response = request.body(body).send().await.map_err(AttemptError::from_request)?;
+    let status = response.status();
+    if status.is_success() {
+        Ok(status.as_u16())
+    } else {
+        Err(AttemptError::from_response(status))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_server::TestServer;
+    use super::*;
+
+    fn config(url: &str) -> WebhookConfig {
+        WebhookConfig {
+            url: url.to_string(),
+            headers: BTreeMap::from([("Authorization".to_string(), "Bearer abc".to_string())]),
+        }
+    }
+
+    #[test]
+    fn signature_matches_a_known_hmac() {
+        // RFC 4231 test case 2
+        assert_eq!(
+            signature("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[tokio::test]
+    async fn signed_posts_carry_the_body_hmac_and_headers() {
+        let server = TestServer::start(vec![204]).await;
+        let event = AlertEvent::test();
+        assert_eq!(send(&config(&server.url), Some("s3cret"), &event).await.unwrap(), 204);
+
+        let requests = server.requests();
+        let request = &requests[0];
+        assert_eq!(request.path, "/hook");
+        assert_eq!(request.headers["x-monitor-signature"], signature("s3cret", &request.body));
+        assert_eq!(request.headers["x-monitor-event"], "test");
+        assert_eq!(request.headers["authorization"], "Bearer abc");
+        assert_eq!(request.headers["content-type"], "application/json");
+        let body = request.json();
+        assert_eq!(body["version"], super::super::PAYLOAD_VERSION);
+        assert_eq!(body["event"], "test");
+        assert_eq!(body["monitor"]["name"], "Test monitor");
+    }
+
+    #[tokio::test]
+    async fn unsigned_posts_have_no_signature() {
+        let server = TestServer::start(vec![200]).await;
+        send(&config(&server.url), None, &AlertEvent::test()).await.unwrap();
+        assert!(!server.requests()[0].headers.contains_key("x-monitor-signature"));
+    }
+
+    #[tokio::test]
+    async fn failed_posts_say_whether_to_retry() {
+        let server = TestServer::start(vec![502, 410]).await;
+        let config = config(&server.url);
+        let error = send(&config, None, &AlertEvent::test()).await.unwrap_err();
+        assert!(error.retryable);
+        assert_eq!(error.status_code, Some(502));
+        let error = send(&config, None, &AlertEvent::test()).await.unwrap_err();
+        assert!(!error.retryable);
+        assert_eq!(error.status_code, Some(410));
+    }
+}
@@ -0,0 +1,113 @@
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Row};
+use serde::Serialize;
+
+use crate::db::Database;
+
+// Longest pause accepted with an end time; leave the end out to pause until resumed
+pub const MAX_PAUSE_DAYS: i64 = 30;
+
+// A stretch of time monitoring was paused, for one monitor or (monitor_id None) for all of them
+#[derive(Debug, Serialize, Clone)]
+pub struct Pause {
+    pub id: i64,
+    pub monitor_id: Option<String>,
+    pub started_at: DateTime<Utc>,
+    // None while the pause is still going on
+    pub ended_at: Option<DateTime<Utc>>,
+    // when it is due to resume by itself, if ever
+    pub paused_until: Option<DateTime<Utc>>,
+}
+
+// The app-wide pause, if monitoring is paused right now
+#[derive(Debug, Serialize, Clone, Copy)]
+pub struct GlobalPause {
+    pub since: DateTime<Utc>,
+    pub until: Option<DateTime<Utc>>,
+}
+
+fn from_ms(ms: i64) -> DateTime<Utc> {
+    DateTime::from_timestamp_millis(ms).unwrap_or_default()
+}
+
+fn pause_from_row(row: &Row) -> rusqlite::Result<Pause> {
+    Ok(Pause {
+        id: row.get(0)?,
+        monitor_id: row.get(1)?,
+        started_at: from_ms(row.get(2)?),
+        ended_at: row.get::<_, Option<i64>>(3)?.map(from_ms),
+        paused_until: row.get::<_, Option<i64>>(4)?.map(from_ms),
+    })
+}
+
+const SELECT_PAUSE: &str = "SELECT id, monitor_id, started_at, ended_at, paused_until FROM pauses";
+
+// Closes any pause still open for the same scope first, so there is only ever one
+pub fn start(
+    db: &Database,
+    monitor_id: Option<&str>,
+    at: DateTime<Utc>,
+    until: Option<DateTime<Utc>>,
+) -> Result<(), String> {
+    db.with_conn(|conn| {
+        let tx = conn.transaction()?;
+        tx.execute(
+            "UPDATE pauses SET ended_at = ?2 WHERE monitor_id IS ?1 AND ended_at IS NULL",
+            params![monitor_id, at.timestamp_millis()],
+        )?;
+        tx.execute(
+            "INSERT INTO pauses (monitor_id, started_at, paused_until) VALUES (?1, ?2, ?3)",
+            params![monitor_id, at.timestamp_millis(), until.map(|t| t.timestamp_millis())],
+        )?;
+        tx.commit()
+    })
+}
+
+pub fn end(db: &Database, monitor_id: Option<&str>, at: DateTime<Utc>) -> Result<(), String> {
+    db.with_conn(|conn| {
+        conn.execute(
+            "UPDATE pauses SET ended_at = ?2 WHERE monitor_id IS ?1 AND ended_at IS NULL",
+            params![monitor_id, at.timestamp_millis()],
+        )?;
+        Ok(())
+    })
+}
+
+// The app-wide pause that was still going on when the app last stopped
+pub fn open_global(db: &Database) -> Result<Option<GlobalPause>, String> {
+    db.with_conn(|conn| {
+        let mut stmt = conn.prepare(&format!(
+            "{} WHERE monitor_id IS NULL AND ended_at IS NULL ORDER BY started_at DESC LIMIT 1",
+            SELECT_PAUSE
+        ))?;
+        let mut rows = stmt.query_map([], pause_from_row)?;
+        rows.next().transpose().map(|pause| {
+            pause.map(|p| GlobalPause {
+                since: p.started_at,
+                until: p.paused_until,
+            })
+        })
+    })
+}
+
+// Pauses overlapping [from, to), oldest first. With a monitor id, app-wide pauses are included.
+pub fn list(
+    db: &Database,
+    monitor_id: Option<&str>,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+) -> Result<Vec<Pause>, String> {
+    let from_ms = from.map(|t| t.timestamp_millis());
+    let to_ms = to.map(|t| t.timestamp_millis());
+    db.with_conn(|conn| {
+        let mut stmt = conn.prepare(&format!(
+            "{} WHERE (?1 IS NULL OR monitor_id = ?1 OR monitor_id IS NULL)
+               AND (?2 IS NULL OR ended_at IS NULL OR ended_at > ?2)
+               AND (?3 IS NULL OR started_at < ?3)
+             ORDER BY started_at",
+            SELECT_PAUSE
+        ))?;
+        let rows = stmt.query_map(params![monitor_id, from_ms, to_ms], pause_from_row)?;
+        rows.collect()
+    })
+}
@@ -0,0 +1,577 @@
+use chrono::{DateTime, Datelike, Local, Utc, Weekday};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use tauri::{AppHandle, Manager};
+
+use crate::clock;
+use crate::config;
+use crate::db::Database;
+use crate::escalations;
+use crate::incidents;
+use crate::maintenance;
+use crate::monitors;
+use crate::notifiers::{self, desktop, AlertEvent, Channel, ChannelKind, Severity};
+
+const RULES_FILE: &str = "routing.json";
+const RULES_FILE_VERSION: u32 = 1;
+// a day is plenty for an unacknowledged incident to reach everyone
+const MAX_ESCALATION_MINS: u32 = 24 * 60;
+// long enough for the catch-up checks after a suspend to come back
+const RESUME_ESCALATION_DELAY: chrono::Duration = chrono::Duration::seconds(60);
+
+// Decides which channels hear about an event. Every condition that is set has to match;
+// empty lists match anything.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RoutingRule {
+    pub id: String,
+    pub name: String,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    // the monitor has at least one of these tags
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub severities: Vec<Severity>,
+    // the state the monitor changed to: up, degraded, down or maintenance
+    #[serde(default)]
+    pub states: Vec<String>,
+    #[serde(default)]
+    pub active_hours: Option<ActiveHours>,
+    pub steps: Vec<EscalationStep>,
+    // don't look at later rules once this one matches
+    #[serde(default)]
+    pub stop: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+// Local time; an end before the start runs past midnight, e.g. 18:00-08:00
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ActiveHours {
+    // empty means every day; for overnight ranges this is the day the range starts
+    #[serde(default)]
+    pub days: Vec<Weekday>,
+    pub start: String,
+    pub end: String,
+}
+
+// Steps after the first only fire while the event's incident is still open and unacknowledged
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EscalationStep {
+    #[serde(default)]
+    pub after_mins: u32,
+    pub channels: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct RoutingRuleInput {
+    pub name: String,
+    pub enabled: Option<bool>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub severities: Vec<Severity>,
+    #[serde(default)]
+    pub states: Vec<String>,
+    pub active_hours: Option<ActiveHours>,
+    pub steps: Vec<EscalationStep>,
+    #[serde(default)]
+    pub stop: bool,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct RulesFile {
+    rules: Vec<RoutingRule>,
+}
+
+// Which channels an event reaches and when; returned by the dry run
+#[derive(Debug, Serialize, Clone)]
+pub struct RoutingPlan {
+    pub matched_rules: Vec<String>,
+    pub deliveries: Vec<PlannedDelivery>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct PlannedDelivery {
+    pub channel_id: String,
+    pub channel_name: String,
+    pub after_mins: u32,
+    // None when no rule matched and the monitor's own channel list was used
+    pub rule_id: Option<String>,
+}
+
+impl ActiveHours {
+    fn validate(&self) -> Result<(), String> {
+        maintenance::parse_time_of_day(&self.start)?;
+        maintenance::parse_time_of_day(&self.end)?;
+        Ok(())
+    }
+
+    fn contains(&self, at: DateTime<Local>) -> bool {
+        let (Ok(start), Ok(end)) = (
+            maintenance::parse_time_of_day(&self.start),
+            maintenance::parse_time_of_day(&self.end),
+        ) else {
+            return false;
+        };
+        let time = at.time();
+        let on = |day: Weekday| self.days.is_empty() || self.days.contains(&day);
+        if start <= end {
+            on(at.weekday()) && start <= time && time < end
+        } else if time >= start {
+            on(at.weekday())
+        } else {
+            // the early-morning part belongs to the range that started yesterday
+            time < end && on(at.weekday().pred())
+        }
+    }
+}
+
+impl RoutingRule {
+    pub fn from_input(input: RoutingRuleInput) -> Result<RoutingRule, String> {
+        let mut rule = RoutingRule {
+            id: monitors::new_id(),
+            name: String::new(),
+            enabled: true,
+            tags: Vec::new(),
+            severities: Vec::new(),
+            states: Vec::new(),
+            active_hours: None,
+            steps: Vec::new(),
+            stop: false,
+        };
+        rule.apply(input)?;
+        Ok(rule)
+    }
+
+    pub fn apply(&mut self, input: RoutingRuleInput) -> Result<(), String> {
+        let name = input.name.trim();
+        if name.is_empty() {
+            return Err("routing rule needs a name".to_string());
+        }
+        let states: Vec<String> = input.states.iter().map(|s| s.trim().to_lowercase()).collect();
+        if let Some(state) = states
+            .iter()
+            .find(|s| !["up", "degraded", "down", "maintenance"].contains(&s.as_str()))
+        {
+            return Err(format!("unknown state '{}'", state));
+        }
+        if let Some(hours) = &input.active_hours {
+            hours.validate()?;
+        }
+        if input.steps.is_empty() {
+            return Err("routing rule needs at least one step".to_string());
+        }
+        let mut steps = Vec::new();
+        for step in input.steps {
+            if step.after_mins > MAX_ESCALATION_MINS {
+                return Err(format!("steps can wait at most {} minutes", MAX_ESCALATION_MINS));
+            }
+            let channels: Vec<String> = step
+                .channels
+                .iter()
+                .map(|id| id.trim().to_string())
+                .filter(|id| !id.is_empty())
+                .collect();
+            if channels.is_empty() {
+                return Err("every step needs at least one channel".to_string());
+            }
+            steps.push(EscalationStep {
+                after_mins: step.after_mins,
+                channels,
+            });
+        }
+        steps.sort_by_key(|step| step.after_mins);
+
+        self.name = name.to_string();
+        if let Some(enabled) = input.enabled {
+            self.enabled = enabled;
+        }
+        self.tags = input
+            .tags
+            .iter()
+            .map(|tag| tag.trim().to_lowercase())
+            .filter(|tag| !tag.is_empty())
+            .collect();
+        self.severities = input.severities;
+        self.states = states;
+        self.active_hours = input.active_hours;
+        self.steps = steps;
+        self.stop = input.stop;
+        Ok(())
+    }
+
+    pub fn matches(&self, event: &AlertEvent, now: DateTime<Utc>) -> bool {
+        self.enabled
+            && (self.tags.is_empty() || self.tags.iter().any(|tag| event.monitor.tags.contains(tag)))
+            && (self.severities.is_empty() || self.severities.contains(&event.severity))
+            && (self.states.is_empty() || self.states.contains(&event.new_state))
+            && self
+                .active_hours
+                .as_ref()
+                .is_none_or(|hours| hours.contains(now.with_timezone(&Local)))
+    }
+}
+
+// Rules are tried in order. When none match, the monitor's own channel list (`routes`) applies
+// as it did before rules existed, plus the desktop for the alerts it has always shown.
+// Escalation steps are left out for events without an incident, since there is nothing that
+// could be acknowledged, and recoveries go nowhere without `notify_recovery`.
+pub fn plan(
+    rules: &[RoutingRule],
+    channels: &[Channel],
+    event: &AlertEvent,
+    routes: Option<&[String]>,
+    notify_recovery: bool,
+    now: DateTime<Utc>,
+) -> RoutingPlan {
+    let mut matched_rules = Vec::new();
+    let mut deliveries: Vec<PlannedDelivery> = Vec::new();
+    let mut add = |channel_id: &str, after_mins: u32, rule_id: Option<&str>| {
+        let Some(channel) = channels.iter().find(|c| c.id == channel_id && c.enabled) else {
+            return;
+        };
+        // a channel that several steps name is only told once, at the earliest step
+        match deliveries.iter_mut().find(|d| d.channel_id == channel_id) {
+            Some(existing) if existing.after_mins <= after_mins => {}
+            Some(existing) => {
+                existing.after_mins = after_mins;
+                existing.rule_id = rule_id.map(String::from);
+            }
+            None => deliveries.push(PlannedDelivery {
+                channel_id: channel.id.clone(),
+                channel_name: channel.name.clone(),
+                after_mins,
+                rule_id: rule_id.map(String::from),
+            }),
+        }
+    };
+
+    for rule in rules.iter().filter(|rule| rule.matches(event, now)) {
+        matched_rules.push(rule.id.clone());
+        for step in &rule.steps {
+            if step.after_mins > 0 && event.incident_id.is_none() {
+                continue;
+            }
+            for channel_id in &step.channels {
+                add(channel_id, step.after_mins, Some(&rule.id));
+            }
+        }
+        if rule.stop {
+            break;
+        }
+    }
+    if matched_rules.is_empty() {
+        for channel in channels.iter().filter(|c| match c.kind {
+            ChannelKind::Desktop(_) => desktop::announces(event),
+            _ => routes.is_none_or(|routes| routes.contains(&c.id)),
+        }) {
+            add(&channel.id, 0, None);
+        }
+    }
+    if event.is_recovery() && !notify_recovery {
+        deliveries.clear();
+    }
+    deliveries.sort_by_key(|d| d.after_mins);
+    RoutingPlan {
+        matched_rules,
+        deliveries,
+    }
+}
+
+// Sends an event to the channels its plan names: the first step right away, later steps
+// are stored and sent by `run_due_escalations` if the incident still needs someone then
+pub fn route(app_handle: &AppHandle, event: AlertEvent, routes: Option<&[String]>, notify_recovery: bool) {
+    let state = app_handle.state::<crate::AppState>();
+    let now = Utc::now();
+    let plan = {
+        let rules = state.routing_rules.lock().unwrap();
+        let channels = state.channels.lock().unwrap();
+        plan(&rules, &channels, &event, routes, notify_recovery, now)
+    };
+    let mut steps: BTreeMap<u32, Vec<String>> = BTreeMap::new();
+    for delivery in plan.deliveries {
+        steps.entry(delivery.after_mins).or_default().push(delivery.channel_id);
+    }
+    for (after_mins, channel_ids) in steps {
+        if after_mins == 0 {
+            notifiers::dispatch(app_handle, event.clone(), Some(&channel_ids));
+            continue;
+        }
+        // without a database there is no incident to wait on
+        let (Some(db), Some(incident_id)) = (&state.db, event.incident_id) else {
+            continue;
+        };
+        let escalation = escalations::Escalation {
+            incident_id,
+            after_mins,
+            channel_ids,
+            event: event.clone(),
+        };
+        let due_at = now + chrono::Duration::minutes(after_mins as i64);
+        if let Err(e) = escalations::schedule(db, due_at, &escalation) {
+            eprintln!("Failed to schedule escalation for incident {}: {}", incident_id, e);
+        }
+    }
+}
+
+// Called by the scheduler loop, so steps fire within a minute of their time
+pub fn run_due_escalations(app_handle: &AppHandle) {
+    let state = app_handle.state::<crate::AppState>();
+    let Some(db) = &state.db else {
+        return;
+    };
+    let due = match escalations::take_due(db, Utc::now()) {
+        Ok(due) => due,
+        Err(e) => {
+            eprintln!("Failed to read escalations: {}", e);
+            return;
+        }
+    };
+    for escalation in due.into_iter().filter(|e| needs_escalation(app_handle, &e.event)) {
+        notifiers::dispatch(app_handle, escalation.event, Some(&escalation.channel_ids));
+    }
+}
+
+// After a suspend the catch-up checks run first, so an incident that ended while the machine
+// slept doesn't escalate
+pub fn clock_jumped(db: &Database, jump: clock::Jump) {
+    let result = match jump {
+        clock::Jump::Gap { .. } => escalations::defer(db, Utc::now() + RESUME_ESCALATION_DELAY),
+        // keep them the same real time away
+        clock::Jump::Backward { secs } => escalations::shift_back(db, secs),
+    };
+    if let Err(e) = result {
+        eprintln!("Failed to reschedule escalations: {}", e);
+    }
+}
+
+// Still open and nobody has acknowledged it, and the monitor hasn't been snoozed, paused or
+// put into maintenance in the meantime
+fn needs_escalation(app_handle: &AppHandle, event: &AlertEvent) -> bool {
+    let state = app_handle.state::<crate::AppState>();
+    let (Some(db), Some(incident_id)) = (&state.db, event.incident_id) else {
+        return false;
+    };
+    let muted = state.websites.lock().unwrap().iter().find(|w| w.monitor.id == event.monitor.id).is_none_or(|w| {
+        w.mute_state().is_muted(Utc::now())
+            || w.state == crate::health::HealthState::Maintenance
+            || crate::is_paused(&state, &w.monitor)
+    });
+    if muted {
+        return false;
+    }
+    match incidents::get(db, incident_id) {
+        Ok(incident) => incident.is_some_and(|incident| incident.status == "open"),
+        Err(e) => {
+            eprintln!("Failed to look up incident {}: {}", incident_id, e);
+            false
+        }
+    }
+}
+
+pub fn load(app_handle: &AppHandle) -> Result<Vec<RoutingRule>, String> {
+    let path = config::config_path(app_handle, RULES_FILE)?;
+    let file: Option<RulesFile> = config::load_versioned(&path, RULES_FILE_VERSION)?;
+    Ok(file.unwrap_or_default().rules)
+}
+
+pub fn save(app_handle: &AppHandle, rules: &[RoutingRule]) -> Result<(), String> {
+    let path = config::config_path(app_handle, RULES_FILE)?;
+    let file = RulesFile { rules: rules.to_vec() };
+    config::save_versioned(&path, RULES_FILE_VERSION, &file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notifiers::webhook::WebhookConfig;
+    use chrono::TimeZone;
+
+    fn local(day: u32, hour: u32, minute: u32) -> DateTime<Local> {
+        // 2024-01-01 was a Monday
+        Local.with_ymd_and_hms(2024, 1, day, hour, minute, 0).single().unwrap()
+    }
+
+    fn hours(days: Vec<Weekday>, start: &str, end: &str) -> ActiveHours {
+        ActiveHours {
+            days,
+            start: start.to_string(),
+            end: end.to_string(),
+        }
+    }
+
+    #[test]
+    fn daytime_hours_include_the_start_but_not_the_end() {
+        let hours = hours(vec![Weekday::Mon], "09:00", "17:00");
+        assert!(hours.contains(local(1, 9, 0)));
+        assert!(hours.contains(local(1, 16, 59)));
+        assert!(!hours.contains(local(1, 17, 0)));
+        assert!(!hours.contains(local(1, 8, 59)));
+        assert!(!hours.contains(local(2, 12, 0)));
+    }
+
+    #[test]
+    fn overnight_hours_belong_to_the_day_they_start() {
+        let hours = hours(vec![Weekday::Fri], "18:00", "08:00");
+        // Friday evening and the early hours of Saturday
+        assert!(hours.contains(local(5, 18, 0)));
+        assert!(hours.contains(local(5, 23, 59)));
+        assert!(hours.contains(local(6, 0, 0)));
+        assert!(hours.contains(local(6, 7, 59)));
+        assert!(!hours.contains(local(6, 8, 0)));
+        // the early hours of Friday belong to Thursday's range
+        assert!(!hours.contains(local(5, 7, 0)));
+        assert!(!hours.contains(local(5, 12, 0)));
+    }
+
+    #[test]
+    fn hours_without_days_apply_every_day() {
+        let hours = hours(Vec::new(), "22:00", "06:00");
+        assert!(hours.contains(local(3, 23, 0)));
+        assert!(hours.contains(local(7, 5, 0)));
+        assert!(!hours.contains(local(7, 12, 0)));
+    }
+
+    fn webhook(id: &str) -> Channel {
+        Channel {
+            id: id.to_string(),
+            name: id.to_string(),
+            enabled: true,
+            kind: ChannelKind::Webhook(WebhookConfig {
+                url: "http://127.0.0.1:9/hook".to_string(),
+                headers: BTreeMap::new(),
+            }),
+            has_secret: false,
+        }
+    }
+
+    fn channels() -> Vec<Channel> {
+        vec![desktop::channel(), webhook("ops"), webhook("email")]
+    }
+
+    fn event(old_state: &str, new_state: &str, incident_id: Option<i64>) -> AlertEvent {
+        let mut event = AlertEvent::test();
+        event.event = "state_changed".to_string();
+        event.monitor.tags = vec!["production".to_string()];
+        event.old_state = old_state.to_string();
+        event.new_state = new_state.to_string();
+        event.incident_id = incident_id;
+        event.down_since = (old_state == "down" || new_state == "down").then(Utc::now);
+        event
+    }
+
+    fn rule(id: &str, states: &[&str], steps: Vec<(u32, &str)>) -> RoutingRule {
+        RoutingRule {
+            id: id.to_string(),
+            name: id.to_string(),
+            enabled: true,
+            tags: Vec::new(),
+            severities: Vec::new(),
+            states: states.iter().map(|s| s.to_string()).collect(),
+            active_hours: None,
+            steps: steps
+                .into_iter()
+                .map(|(after_mins, channel)| EscalationStep {
+                    after_mins,
+                    channels: vec![channel.to_string()],
+                })
+                .collect(),
+            stop: false,
+        }
+    }
+
+    fn channel_ids(plan: &RoutingPlan) -> Vec<(&str, u32)> {
+        plan.deliveries.iter().map(|d| (d.channel_id.as_str(), d.after_mins)).collect()
+    }
+
+    #[test]
+    fn without_rules_the_monitor_routes_and_the_desktop_apply() {
+        let event = event("up", "down", Some(1));
+        let all = plan(&[], &channels(), &event, None, true, Utc::now());
+        assert_eq!(channel_ids(&all), vec![("desktop", 0), ("ops", 0), ("email", 0)]);
+
+        let routes = vec!["email".to_string()];
+        let routed = plan(&[], &channels(), &event, Some(&routes), true, Utc::now());
+        assert_eq!(channel_ids(&routed), vec![("desktop", 0), ("email", 0)]);
+
+        let desktop_only = plan(&[], &channels(), &event, Some(&[]), true, Utc::now());
+        assert_eq!(channel_ids(&desktop_only), vec![("desktop", 0)]);
+    }
+
+    #[test]
+    fn recoveries_go_nowhere_without_notify_recovery() {
+        let recovery = event("down", "up", Some(1));
+        let quiet = plan(&[], &channels(), &recovery, None, false, Utc::now());
+        assert!(quiet.deliveries.is_empty());
+        let told = plan(&[], &channels(), &recovery, None, true, Utc::now());
+        assert_eq!(channel_ids(&told), vec![("desktop", 0), ("ops", 0), ("email", 0)]);
+
+        // rules that match recoveries are held back too, though the preview still shows them matching
+        let rules = vec![rule("recoveries", &["up"], vec![(0, "ops")])];
+        let quiet = plan(&rules, &channels(), &recovery, None, false, Utc::now());
+        assert_eq!(quiet.matched_rules, vec!["recoveries".to_string()]);
+        assert!(quiet.deliveries.is_empty());
+        let told = plan(&rules, &channels(), &recovery, None, true, Utc::now());
+        assert_eq!(channel_ids(&told), vec![("ops", 0)]);
+
+        // an outage isn't a recovery
+        let down = plan(&[], &channels(), &event("up", "down", Some(1)), Some(&[]), false, Utc::now());
+        assert_eq!(channel_ids(&down), vec![("desktop", 0)]);
+    }
+
+    #[test]
+    fn matching_rules_replace_the_monitor_routes() {
+        let rules = vec![rule("outage", &["down"], vec![(0, "desktop"), (5, "ops"), (15, "email")])];
+        let plan = plan(&rules, &channels(), &event("up", "down", Some(1)), None, true, Utc::now());
+        assert_eq!(plan.matched_rules, vec!["outage".to_string()]);
+        assert_eq!(channel_ids(&plan), vec![("desktop", 0), ("ops", 5), ("email", 15)]);
+        assert!(plan.deliveries.iter().all(|d| d.rule_id.as_deref() == Some("outage")));
+    }
+
+    #[test]
+    fn escalation_steps_need_an_incident() {
+        let rules = vec![rule("slow", &[], vec![(0, "ops"), (5, "email")])];
+        let plan = plan(&rules, &channels(), &event("up", "degraded", None), None, true, Utc::now());
+        assert_eq!(channel_ids(&plan), vec![("ops", 0)]);
+    }
+
+    #[test]
+    fn a_channel_named_twice_is_told_at_the_earliest_step() {
+        let rules = vec![
+            rule("late", &["down"], vec![(15, "ops")]),
+            rule("early", &["down"], vec![(5, "ops")]),
+        ];
+        let plan = plan(&rules, &channels(), &event("up", "down", Some(1)), None, true, Utc::now());
+        assert_eq!(channel_ids(&plan), vec![("ops", 5)]);
+        assert_eq!(plan.deliveries[0].rule_id.as_deref(), Some("early"));
+    }
+
+    #[test]
+    fn stop_skips_later_rules_and_disabled_channels_are_left_out() {
+        let mut first = rule("first", &[], vec![(0, "ops")]);
+        first.stop = true;
+        let rules = vec![first, rule("second", &[], vec![(0, "email")])];
+        let mut channels = channels();
+        let plan_all = plan(&rules, &channels, &event("up", "down", Some(1)), None, true, Utc::now());
+        assert_eq!(channel_ids(&plan_all), vec![("ops", 0)]);
+
+        channels[1].enabled = false;
+        let plan_off = plan(&rules, &channels, &event("up", "down", Some(1)), None, true, Utc::now());
+        assert_eq!(plan_off.matched_rules, vec!["first".to_string()]);
+        assert!(plan_off.deliveries.is_empty());
+    }
+
+    #[test]
+    fn rules_match_on_tags_and_states() {
+        let mut tagged = rule("tagged", &["down"], vec![(0, "ops")]);
+        tagged.tags = vec!["staging".to_string()];
+        let now = Utc::now();
+        assert!(!tagged.matches(&event("up", "down", Some(1)), now));
+        tagged.tags.push("production".to_string());
+        assert!(tagged.matches(&event("up", "down", Some(1)), now));
+        assert!(!tagged.matches(&event("down", "up", Some(1)), now));
+    }
+}
@@ -0,0 +1,169 @@
+use chrono::Local;
+use rand::Rng;
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager};
+use tokio::sync::Notify;
+use tokio::time::{Duration, Instant};
+
+use crate::monitors::Schedule;
+use crate::{AppState, Website};
+
+// Longest the loop sleeps when nothing is due, so it notices new monitors even if a wake-up is missed
+const IDLE_WAKE: Duration = Duration::from_secs(60);
+// After waking from sleep, give Wi-Fi a moment to reconnect before the catch-up check
+const RESUME_SETTLE: Duration = Duration::from_secs(10);
+
+// Gives every monitor its own cadence. Manual checks never move a monitor's next due time,
+// but they do claim it, so a monitor is never checked twice at once.
+#[derive(Default)]
+pub struct Scheduler {
+    next_due: Mutex<HashMap<String, Instant>>,
+    in_flight: Mutex<HashSet<String>>,
+    wake: Notify,
+}
+
+impl Scheduler {
+    // Check this monitor as soon as possible, then carry on with its normal schedule
+    pub fn schedule_now(&self, id: &str) {
+        self.next_due.lock().unwrap().insert(id.to_string(), Instant::now());
+        self.wake.notify_one();
+    }
+
+    pub fn wake(&self) {
+        self.wake.notify_one();
+    }
+
+    pub fn remove(&self, id: &str) {
+        self.next_due.lock().unwrap().remove(id);
+    }
+
+    // Marks the monitors as running and returns those that weren't already
+    pub fn claim(&self, websites: Vec<Website>) -> Vec<Website> {
+        let mut in_flight = self.in_flight.lock().unwrap();
+        websites.into_iter().filter(|w| in_flight.insert(w.monitor.id.clone())).collect()
+    }
+
+    pub fn release(&self, ids: &[String]) {
+        let mut in_flight = self.in_flight.lock().unwrap();
+        for id in ids {
+            in_flight.remove(id);
+        }
+    }
+
+    // Every monitor runs once at `at`, then carries on with its normal schedule. Replaces
+    // whatever was booked, so slots missed while asleep collapse into a single check.
+    fn catch_up(&self, at: Instant) {
+        for due in self.next_due.lock().unwrap().values_mut() {
+            *due = at;
+        }
+    }
+
+    pub async fn run(&self, app_handle: AppHandle) {
+        let mut planned = Duration::ZERO;
+        loop {
+            if let Some(jump) = app_handle.state::<AppState>().clock.observe(planned) {
+                crate::clock_jumped(&app_handle, jump);
+                self.catch_up(Instant::now() + RESUME_SETTLE);
+            }
+            // at most IDLE_WAKE late, which is fine for pause end times
+            crate::resume_expired_pauses(&app_handle);
+            crate::routing::run_due_escalations(&app_handle);
+            let due = self.take_due(&app_handle);
+            if !due.is_empty() {
+                self.dispatch(&app_handle, due);
+            }
+
+            let sleep_until = self
+                .next_due
+                .lock()
+                .unwrap()
+                .values()
+                .min()
+                .copied()
+                .unwrap_or_else(|| Instant::now() + IDLE_WAKE)
+                .min(Instant::now() + IDLE_WAKE);
+            planned = sleep_until.saturating_duration_since(Instant::now());
+            tokio::select! {
+                _ = tokio::time::sleep_until(sleep_until) => {}
+                _ = self.wake.notified() => {}
+            }
+        }
+    }
+
+    // Returns the monitors whose time has come and books their next run
+    fn take_due(&self, app_handle: &AppHandle) -> Vec<Website> {
+        let state = app_handle.state::<AppState>();
+        // while everything is paused, due monitors stay due and run once on resume
+        if state.paused.lock().unwrap().is_some() {
+            return Vec::new();
+        }
+        let websites = state.websites.lock().unwrap().clone();
+        let in_flight = self.in_flight.lock().unwrap();
+        let mut next_due = self.next_due.lock().unwrap();
+        let now = Instant::now();
+
+        next_due.retain(|id, _| websites.iter().any(|w| &w.monitor.id == id));
+        let mut due = Vec::new();
+        for website in websites {
+            if !website.monitor.enabled {
+                // paused; resuming starts it afresh like a new monitor
+                next_due.remove(&website.monitor.id);
+                continue;
+            }
+            let schedule = &website.monitor.schedule;
+            let Some(at) = next_due.get(&website.monitor.id).copied() else {
+                // first sighting (startup): spread monitors out by their jitter
+                next_due.insert(website.monitor.id.clone(), now + start_jitter(schedule));
+                continue;
+            };
+            if at > now {
+                continue;
+            }
+            next_due.insert(website.monitor.id.clone(), next_run(schedule, at, now));
+            // a slow check is still running; skip this slot rather than pile up
+            if in_flight.contains(&website.monitor.id) {
+                continue;
+            }
+            due.push(website);
+        }
+        due
+    }
+
+    fn dispatch(&self, app_handle: &AppHandle, due: Vec<Website>) {
+        let due = self.claim(due);
+        let ids: Vec<String> = due.iter().map(|w| w.monitor.id.clone()).collect();
+
+        let app_handle = app_handle.clone();
+        tauri::async_runtime::spawn(async move {
+            let _ = crate::do_check_websites(due, app_handle.clone()).await;
+            app_handle.state::<AppState>().scheduler.release(&ids);
+        });
+    }
+}
+
+fn start_jitter(schedule: &Schedule) -> Duration {
+    if schedule.jitter_secs == 0 {
+        return Duration::ZERO;
+    }
+    Duration::from_millis(rand::thread_rng().gen_range(0..=schedule.jitter_secs * 1000))
+}
+
+// Next run after one that was due at `due`. Interval schedules keep their cadence
+// (due + interval) unless we have fallen behind, in which case they restart from now.
+fn next_run(schedule: &Schedule, due: Instant, now: Instant) -> Instant {
+    if let Ok(Some(cron)) = schedule.cron_schedule() {
+        let local_now = Local::now();
+        if let Some(next) = cron.after(&local_now).next() {
+            let wait = (next - local_now).to_std().unwrap_or(Duration::ZERO);
+            return now + wait;
+        }
+    }
+    let interval = Duration::from_secs(schedule.interval_secs.max(1));
+    let next = due + interval;
+    if next > now {
+        next
+    } else {
+        now + interval
+    }
+}
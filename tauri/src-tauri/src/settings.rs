@@ -0,0 +1,94 @@
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+
+use crate::config;
+
+const SETTINGS_FILE: &str = "settings.json";
+const SETTINGS_FILE_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct AppSettings {
+    // how many sites may be checked at the same time
+    pub max_concurrent_checks: usize,
+    // how many of those may target the same host
+    pub max_checks_per_host: usize,
+    // warn when a certificate is this many days (or fewer) from expiry
+    pub certificate_warning_days: Vec<u32>,
+    // send a "back up" notification when a down monitor recovers
+    pub notify_recovery: bool,
+    // link to an incident in alerts, e.g. "https://status.example.com/incidents/{incident_id}"
+    pub incident_url_template: Option<String>,
+    // Microsoft Graph endpoint; point it at a local stand-in for testing
+    pub graph_base_url: String,
+    // reached when sites fail, to tell a dead connection from dead sites; when empty, only a
+    // pass where every site fails to connect counts as offline
+    pub canary_targets: Vec<String>,
+}
+
+impl Default for AppSettings {
+    fn default() -> Self {
+        AppSettings {
+            max_concurrent_checks: 8,
+            max_checks_per_host: 2,
+            certificate_warning_days: vec![30, 14, 3],
+            notify_recovery: true,
+            incident_url_template: None,
+            graph_base_url: "https://graph.microsoft.com/v1.0".to_string(),
+            // opt in: nothing outside the user's own monitors is contacted unless they add targets
+            canary_targets: Vec::new(),
+        }
+    }
+}
+
+impl AppSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_concurrent_checks == 0 {
+            return Err("max_concurrent_checks must be at least 1".to_string());
+        }
+        if self.max_checks_per_host == 0 {
+            return Err("max_checks_per_host must be at least 1".to_string());
+        }
+        let graph = url::Url::parse(&self.graph_base_url).map_err(|e| format!("invalid graph_base_url: {}", e))?;
+        if !matches!(graph.scheme(), "http" | "https") {
+            return Err("graph_base_url must use http or https".to_string());
+        }
+        crate::network::validate_canaries(&self.canary_targets)?;
+        if let Some(template) = &self.incident_url_template {
+            if !template.contains("{incident_id}") {
+                return Err("incident_url_template must contain {incident_id}".to_string());
+            }
+            url::Url::parse(&template.replace("{incident_id}", "1"))
+                .map_err(|e| format!("invalid incident_url_template: {}", e))?;
+        }
+        Ok(())
+    }
+
+    pub fn incident_url(&self, incident_id: i64) -> Option<String> {
+        self.incident_url_template
+            .as_ref()
+            .map(|template| template.replace("{incident_id}", &incident_id.to_string()))
+    }
+}
+
+pub fn load(app_handle: &AppHandle) -> AppSettings {
+    let loaded = config::config_path(app_handle, SETTINGS_FILE)
+        .and_then(|path| config::load_versioned::<AppSettings>(&path, SETTINGS_FILE_VERSION));
+    match loaded {
+        Ok(Some(settings)) if settings.validate().is_ok() => settings,
+        Ok(Some(_)) => {
+            eprintln!("Ignoring invalid settings file, using defaults");
+            AppSettings::default()
+        }
+        Ok(None) => AppSettings::default(),
+        Err(e) => {
+            eprintln!("Failed to load settings, using defaults: {}", e);
+            AppSettings::default()
+        }
+    }
+}
+
+pub fn save(app_handle: &AppHandle, settings: &AppSettings) -> Result<(), String> {
+    let path = config::config_path(app_handle, SETTINGS_FILE)?;
+    config::save_versioned(&path, SETTINGS_FILE_VERSION, settings)
+}
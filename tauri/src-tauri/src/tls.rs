@@ -0,0 +1,446 @@
+use chrono::{DateTime, Utc};
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::CryptoProvider;
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{ClientConfig, DigitallySignedStruct, SignatureScheme};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex, OnceLock};
+use tauri::AppHandle;
+use tokio::net::TcpStream;
+use tokio::time::{timeout, Duration, Instant};
+use x509_parser::extensions::GeneralName;
+
+use crate::config;
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+// Certificates rarely change, so each host is probed at most this often
+const PROBE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+const CERTIFICATES_FILE: &str = "certificates.json";
+const CERTIFICATES_FILE_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CertificateInfo {
+    pub subject: String,
+    pub subject_alt_names: Vec<String>,
+    pub issuer: String,
+    pub not_before: String,
+    pub not_after: String,
+    // SHA-256 of the leaf certificate, colon separated hex
+    pub fingerprint_sha256: String,
+    // fingerprints of every certificate the server sent, leaf first
+    pub chain_fingerprints: Vec<String>,
+    pub hostname_matches: bool,
+}
+
+impl CertificateInfo {
+    pub fn days_remaining(&self) -> Option<i64> {
+        let not_after = DateTime::parse_from_rfc3339(&self.not_after).ok()?;
+        Some((not_after.with_timezone(&Utc) - Utc::now()).num_days())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CertificateAlert {
+    Expiring { days_remaining: i64, threshold_days: u32 },
+    Expired,
+    HostnameMismatch,
+    ChainChanged,
+}
+
+impl CertificateAlert {
+    pub fn describe(&self, host: &str) -> String {
+        match self {
+            CertificateAlert::Expiring { days_remaining, .. } => {
+                format!("The certificate for {} expires in {} day(s)", host, days_remaining)
+            }
+            CertificateAlert::Expired => format!("The certificate for {} has expired", host),
+            CertificateAlert::HostnameMismatch => {
+                format!("The certificate served by {} is not valid for that host name", host)
+            }
+            CertificateAlert::ChainChanged => {
+                format!("The certificate chain served by {} has changed", host)
+            }
+        }
+    }
+}
+
+// What we last told the user about a monitor's certificate, so each alert fires once.
+// Saved to certificates.json so a restart neither repeats alerts nor misses a chain change.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct CertificateAlertState {
+    // the certificate last seen, leaf and chain
+    pub fingerprint_sha256: Option<String>,
+    pub chain_fingerprints: Vec<String>,
+    // lowest expiry threshold already notified (0 once expired)
+    pub notified_threshold_days: Option<u32>,
+    pub notified_mismatch: bool,
+}
+
+// Compares a fresh probe against the last certificate seen and returns the alerts that are newly due
+pub fn evaluate(
+    current: &CertificateInfo,
+    warning_days: &[u32],
+    alerted: &mut CertificateAlertState,
+) -> Vec<CertificateAlert> {
+    let mut alerts = Vec::new();
+
+    if let Some(fingerprint) = &alerted.fingerprint_sha256 {
+        if alerted.chain_fingerprints != current.chain_fingerprints {
+            alerts.push(CertificateAlert::ChainChanged);
+        }
+        if *fingerprint != current.fingerprint_sha256 {
+            // a new leaf (usually a renewal) starts the expiry countdown again
+            alerted.notified_threshold_days = None;
+        }
+    }
+    alerted.fingerprint_sha256 = Some(current.fingerprint_sha256.clone());
+    alerted.chain_fingerprints = current.chain_fingerprints.clone();
+
+    if !current.hostname_matches {
+        if !alerted.notified_mismatch {
+            alerts.push(CertificateAlert::HostnameMismatch);
+            alerted.notified_mismatch = true;
+        }
+    } else {
+        alerted.notified_mismatch = false;
+    }
+
+    if let Some(days) = current.days_remaining() {
+        if days < 0 {
+            if alerted.notified_threshold_days != Some(0) {
+                alerts.push(CertificateAlert::Expired);
+                alerted.notified_threshold_days = Some(0);
+            }
+        } else if let Some(threshold) = warning_days.iter().copied().filter(|t| days <= *t as i64).min() {
+            if alerted.notified_threshold_days.map(|n| threshold < n).unwrap_or(true) {
+                alerts.push(CertificateAlert::Expiring {
+                    days_remaining: days,
+                    threshold_days: threshold,
+                });
+                alerted.notified_threshold_days = Some(threshold);
+            }
+        }
+    }
+
+    alerts
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct CertificatesFile {
+    monitors: HashMap<String, CertificateAlertState>,
+}
+
+pub fn load_alert_states(app_handle: &AppHandle) -> Result<HashMap<String, CertificateAlertState>, String> {
+    let path = config::config_path(app_handle, CERTIFICATES_FILE)?;
+    let file: Option<CertificatesFile> = config::load_versioned(&path, CERTIFICATES_FILE_VERSION)?;
+    Ok(file.unwrap_or_default().monitors)
+}
+
+pub fn save_alert_state(app_handle: &AppHandle, monitor_id: &str, alerted: &CertificateAlertState) -> Result<(), String> {
+    let mut monitors = load_alert_states(app_handle)?;
+    monitors.insert(monitor_id.to_string(), alerted.clone());
+    let path = config::config_path(app_handle, CERTIFICATES_FILE)?;
+    config::save_versioned(&path, CERTIFICATES_FILE_VERSION, &CertificatesFile { monitors })
+}
+
+// The last probe of each host and port. Checks in between reuse its answer, so every monitor
+// of a host still sees the certificate without a handshake on each check.
+#[derive(Default)]
+pub struct ProbeCache {
+    // keyed by host and port
+    probes: Mutex<HashMap<(String, u16), Probed>>,
+}
+
+struct Probed {
+    at: Instant,
+    result: Result<CertificateInfo, String>,
+}
+
+impl ProbeCache {
+    pub async fn probe(&self, host: &str, port: u16) -> Result<CertificateInfo, String> {
+        let key = (host.to_string(), port);
+        if let Some(probed) = self.probes.lock().unwrap().get(&key) {
+            if probed.at.elapsed() < PROBE_INTERVAL {
+                return probed.result.clone();
+            }
+        }
+        let result = probe(host, port).await;
+        let probed = Probed {
+            at: Instant::now(),
+            result: result.clone(),
+        };
+        self.probes.lock().unwrap().insert(key, probed);
+        result
+    }
+}
+
+// Connects to the host and records the certificate chain it serves. Verification is
+// deliberately switched off: we want to see (and report) bad certificates, not fail on them.
+async fn probe(host: &str, port: u16) -> Result<CertificateInfo, String> {
+    let connector = tokio_rustls::TlsConnector::from(client_config());
+    let server_name = ServerName::try_from(host.to_string()).map_err(|e| e.to_string())?;
+
+    let handshake = async {
+        let stream = TcpStream::connect((host, port)).await.map_err(|e| e.to_string())?;
+        connector
+            .connect(server_name, stream)
+            .await
+            .map_err(|e| e.to_string())
+    };
+    let tls = timeout(PROBE_TIMEOUT, handshake)
+        .await
+        .map_err(|_| "TLS handshake timed out".to_string())??;
+
+    let (_, connection) = tls.get_ref();
+    let chain = connection
+        .peer_certificates()
+        .filter(|c| !c.is_empty())
+        .ok_or_else(|| "server sent no certificate".to_string())?;
+    describe_chain(host, chain)
+}
+
+fn describe_chain(host: &str, chain: &[CertificateDer<'_>]) -> Result<CertificateInfo, String> {
+    let (_, leaf) = x509_parser::parse_x509_certificate(chain[0].as_ref())
+        .map_err(|e| format!("could not parse certificate: {}", e))?;
+
+    let mut subject_alt_names = Vec::new();
+    let mut san_ips = Vec::new();
+    if let Ok(Some(san)) = leaf.subject_alternative_name() {
+        for name in &san.value.general_names {
+            match name {
+                GeneralName::DNSName(dns) => subject_alt_names.push(dns.to_string()),
+                GeneralName::IPAddress(bytes) => {
+                    if let Some(ip) = ip_from_bytes(bytes) {
+                        subject_alt_names.push(ip.to_string());
+                        san_ips.push(ip);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    let common_name = leaf
+        .subject()
+        .iter_common_name()
+        .next()
+        .and_then(|cn| cn.as_str().ok())
+        .map(|cn| cn.to_string());
+
+    let hostname_matches = match host.parse::<IpAddr>() {
+        Ok(ip) => san_ips.contains(&ip),
+        Err(_) => {
+            let dns_names: Vec<&str> = subject_alt_names
+                .iter()
+                .map(|s| s.as_str())
+                .filter(|s| s.parse::<IpAddr>().is_err())
+                .collect();
+            if dns_names.is_empty() {
+                // legacy certificates without SANs identify the host in the CN
+                common_name.as_deref().map(|cn| dns_name_matches(cn, host)).unwrap_or(false)
+            } else {
+                dns_names.iter().any(|pattern| dns_name_matches(pattern, host))
+            }
+        }
+    };
+
+    let timestamp = |t: i64| {
+        DateTime::<Utc>::from_timestamp(t, 0)
+            .map(|d| d.to_rfc3339())
+            .unwrap_or_default()
+    };
+    let chain_fingerprints: Vec<String> = chain.iter().map(|c| fingerprint(c.as_ref())).collect();
+
+    Ok(CertificateInfo {
+        subject: leaf.subject().to_string(),
+        subject_alt_names,
+        issuer: leaf.issuer().to_string(),
+        not_before: timestamp(leaf.validity().not_before.timestamp()),
+        not_after: timestamp(leaf.validity().not_after.timestamp()),
+        fingerprint_sha256: chain_fingerprints[0].clone(),
+        chain_fingerprints,
+        hostname_matches,
+    })
+}
+
+// RFC 6125 style matching: a leading "*." matches exactly one label
+fn dns_name_matches(pattern: &str, host: &str) -> bool {
+    let pattern = pattern.trim_end_matches('.').to_ascii_lowercase();
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+    match pattern.strip_prefix("*.") {
+        Some(suffix) => host
+            .split_once('.')
+            .map(|(label, rest)| !label.is_empty() && rest == suffix)
+            .unwrap_or(false),
+        None => pattern == host,
+    }
+}
+
+fn ip_from_bytes(bytes: &[u8]) -> Option<IpAddr> {
+    match bytes.len() {
+        4 => Some(IpAddr::from(<[u8; 4]>::try_from(bytes).ok()?)),
+        16 => Some(IpAddr::from(<[u8; 16]>::try_from(bytes).ok()?)),
+        _ => None,
+    }
+}
+
+fn fingerprint(der: &[u8]) -> String {
+    Sha256::digest(der)
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+fn client_config() -> Arc<ClientConfig> {
+    static CONFIG: OnceLock<Arc<ClientConfig>> = OnceLock::new();
+    CONFIG
+        .get_or_init(|| {
+            let provider = Arc::new(rustls::crypto::ring::default_provider());
+            let config = ClientConfig::builder_with_provider(provider.clone())
+                .with_safe_default_protocol_versions()
+                .expect("ring provider supports the default protocol versions")
+                .dangerous()
+                .with_custom_certificate_verifier(Arc::new(RecordOnlyVerifier(provider)))
+                .with_no_client_auth();
+            Arc::new(config)
+        })
+        .clone()
+}
+
+// Accepts any certificate but still checks handshake signatures, so the chain we record
+// really belongs to the server we talked to
+#[derive(Debug)]
+struct RecordOnlyVerifier(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for RecordOnlyVerifier {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(message, cert, dss, &self.0.signature_verification_algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(message, cert, dss, &self.0.signature_verification_algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WARNING_DAYS: &[u32] = &[30, 14, 7];
+
+    fn certificate(fingerprint: &str, days: i64) -> CertificateInfo {
+        // an hour over, so the whole-day count doesn't depend on how long the test takes
+        let not_after = Utc::now() + chrono::Duration::days(days) + chrono::Duration::hours(1);
+        CertificateInfo {
+            subject: "CN=example.com".to_string(),
+            subject_alt_names: vec!["example.com".to_string()],
+            issuer: "CN=Test CA".to_string(),
+            not_before: "2024-01-01T00:00:00Z".to_string(),
+            not_after: not_after.to_rfc3339(),
+            fingerprint_sha256: fingerprint.to_string(),
+            chain_fingerprints: vec![fingerprint.to_string(), "ca".to_string()],
+            hostname_matches: true,
+        }
+    }
+
+    #[test]
+    fn each_expiry_threshold_alerts_once() {
+        let mut alerted = CertificateAlertState::default();
+        assert!(evaluate(&certificate("a", 60), WARNING_DAYS, &mut alerted).is_empty());
+        assert_eq!(
+            evaluate(&certificate("a", 20), WARNING_DAYS, &mut alerted),
+            vec![CertificateAlert::Expiring {
+                days_remaining: 20,
+                threshold_days: 30
+            }]
+        );
+        assert!(evaluate(&certificate("a", 19), WARNING_DAYS, &mut alerted).is_empty());
+        assert_eq!(
+            evaluate(&certificate("a", 6), WARNING_DAYS, &mut alerted),
+            vec![CertificateAlert::Expiring {
+                days_remaining: 6,
+                threshold_days: 7
+            }]
+        );
+        assert_eq!(
+            evaluate(&certificate("a", -2), WARNING_DAYS, &mut alerted),
+            vec![CertificateAlert::Expired]
+        );
+        assert!(evaluate(&certificate("a", -3), WARNING_DAYS, &mut alerted).is_empty());
+    }
+
+    #[test]
+    fn a_new_certificate_reports_the_change_and_restarts_the_countdown() {
+        let mut alerted = CertificateAlertState::default();
+        // the first certificate seen is not a change
+        evaluate(&certificate("old", 5), WARNING_DAYS, &mut alerted);
+        assert_eq!(alerted.notified_threshold_days, Some(7));
+
+        let alerts = evaluate(&certificate("new", 5), WARNING_DAYS, &mut alerted);
+        assert_eq!(
+            alerts,
+            vec![
+                CertificateAlert::ChainChanged,
+                CertificateAlert::Expiring {
+                    days_remaining: 5,
+                    threshold_days: 7
+                }
+            ]
+        );
+        assert_eq!(alerted.fingerprint_sha256.as_deref(), Some("new"));
+    }
+
+    #[test]
+    fn a_hostname_mismatch_alerts_again_only_after_it_was_fixed() {
+        let mut alerted = CertificateAlertState::default();
+        let mut wrong = certificate("a", 90);
+        wrong.hostname_matches = false;
+        assert_eq!(evaluate(&wrong, WARNING_DAYS, &mut alerted), vec![CertificateAlert::HostnameMismatch]);
+        assert!(evaluate(&wrong, WARNING_DAYS, &mut alerted).is_empty());
+        assert!(evaluate(&certificate("a", 90), WARNING_DAYS, &mut alerted).is_empty());
+        assert_eq!(evaluate(&wrong, WARNING_DAYS, &mut alerted), vec![CertificateAlert::HostnameMismatch]);
+    }
+
+    #[test]
+    fn alert_state_survives_a_save_and_load() {
+        let mut alerted = CertificateAlertState::default();
+        evaluate(&certificate("a", 10), WARNING_DAYS, &mut alerted);
+        let file = CertificatesFile {
+            monitors: HashMap::from([("m".to_string(), alerted.clone())]),
+        };
+        let json = serde_json::to_string(&file).unwrap();
+
+        let mut restored = serde_json::from_str::<CertificatesFile>(&json).unwrap().monitors.remove("m").unwrap();
+        assert_eq!(restored, alerted);
+        assert!(evaluate(&certificate("a", 10), WARNING_DAYS, &mut restored).is_empty());
+    }
+}
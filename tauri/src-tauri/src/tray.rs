@@ -0,0 +1,383 @@
+use chrono::{DateTime, Local, Utc};
+use std::sync::Mutex;
+use tauri::image::Image;
+use tauri::menu::{IsMenuItem, MenuBuilder, MenuEvent, MenuItem, PredefinedMenuItem, Submenu, SubmenuBuilder};
+use tauri::tray::{MouseButton, MouseButtonState, TrayIcon, TrayIconBuilder, TrayIconEvent};
+use tauri::{App, AppHandle, Manager, Wry};
+use tauri_plugin_opener::OpenerExt;
+
+use crate::health::HealthState;
+use crate::pauses::GlobalPause;
+use crate::{AppState, Website};
+
+// Drawn when the app has no window icon to put the status dot on
+const FALLBACK_ICON_SIZE: u32 = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    // nothing is being checked (no monitors, or all paused)
+    Idle,
+    // the canaries don't answer, so site results are on hold
+    Offline,
+    Up,
+    Degraded,
+    Down,
+}
+
+impl Status {
+    fn rgb(&self) -> Option<[u8; 3]> {
+        match self {
+            Status::Idle => None,
+            Status::Offline => Some([0x8b, 0x94, 0x9e]),
+            Status::Up => Some([0x2e, 0xa0, 0x43]),
+            Status::Degraded => Some([0xe3, 0xa0, 0x08]),
+            Status::Down => Some([0xd7, 0x3a, 0x49]),
+        }
+    }
+}
+
+// Everything the menu shows; the menu is only rebuilt when this changes
+#[derive(Debug, Clone, PartialEq)]
+struct MenuContents {
+    window_visible: bool,
+    monitoring_paused: bool,
+    monitors: Vec<MonitorEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct MonitorEntry {
+    id: String,
+    name: String,
+    state: HealthState,
+    enabled: bool,
+    snoozed: bool,
+}
+
+#[derive(Default)]
+struct Rendered {
+    status: Option<Status>,
+    tooltip: String,
+    menu: Option<MenuContents>,
+}
+
+// The tray icon plus what was last rendered into it, so refreshes only touch what changed
+pub struct Tray {
+    icon: TrayIcon,
+    window_visible: Mutex<bool>,
+    rendered: Mutex<Rendered>,
+}
+
+pub fn create(app: &App) -> tauri::Result<Tray> {
+    let icon = TrayIconBuilder::new()
+        .icon(status_icon(app.handle(), Status::Idle))
+        .on_menu_event(handle_menu_event)
+        .on_tray_icon_event(|tray, event| {
+            if let TrayIconEvent::Click {
+                button: MouseButton::Left,
+                button_state: MouseButtonState::Up,
+                ..
+            } = event
+            {
+                set_window_visible(tray.app_handle(), true);
+            }
+        })
+        .build(app)?;
+    Ok(Tray {
+        icon,
+        // the window starts out visible
+        window_visible: Mutex::new(true),
+        rendered: Mutex::new(Rendered::default()),
+    })
+}
+
+// Brings the icon, tooltip and menu in line with the current monitor states
+pub fn refresh(app_handle: &AppHandle) {
+    let state = app_handle.state::<AppState>();
+    let websites = state.websites.lock().unwrap().clone();
+    let pause = *state.paused.lock().unwrap();
+    let now = Utc::now();
+    let menu = MenuContents {
+        window_visible: *state.tray.window_visible.lock().unwrap(),
+        monitoring_paused: pause.is_some(),
+        monitors: websites
+            .iter()
+            .map(|w| MonitorEntry {
+                id: w.monitor.id.clone(),
+                name: w.monitor.name.clone(),
+                state: w.state,
+                enabled: w.monitor.enabled,
+                snoozed: w.snoozed_until.is_some_and(|until| until > now),
+            })
+            .collect(),
+    };
+    let network = state.network.status();
+    let status = if pause.is_some() {
+        Status::Idle
+    } else if !network.online {
+        Status::Offline
+    } else {
+        overall_status(&websites)
+    };
+    let tooltip = tooltip(&websites, pause, network.offline_since);
+
+    let mut rendered = state.tray.rendered.lock().unwrap();
+    if rendered.status != Some(status) {
+        match state.tray.icon.set_icon(Some(status_icon(app_handle, status))) {
+            Ok(()) => rendered.status = Some(status),
+            Err(e) => eprintln!("Failed to update tray icon: {}", e),
+        }
+    }
+    if rendered.tooltip != tooltip {
+        match state.tray.icon.set_tooltip(Some(&tooltip)) {
+            Ok(()) => rendered.tooltip = tooltip,
+            Err(e) => eprintln!("Failed to update tray tooltip: {}", e),
+        }
+    }
+    if rendered.menu.as_ref() != Some(&menu) {
+        match build_menu(app_handle, &menu).and_then(|built| state.tray.icon.set_menu(Some(built))) {
+            Ok(()) => rendered.menu = Some(menu),
+            Err(e) => eprintln!("Failed to update tray menu: {}", e),
+        }
+    }
+}
+
+pub fn set_window_visible(app_handle: &AppHandle, visible: bool) {
+    if let Some(window) = app_handle.get_webview_window("main") {
+        let result = if visible {
+            window.show().and_then(|_| window.set_focus())
+        } else {
+            window.hide()
+        };
+        if let Err(e) = result {
+            eprintln!("Failed to {} window: {}", if visible { "show" } else { "hide" }, e);
+        }
+    }
+    *app_handle.state::<AppState>().tray.window_visible.lock().unwrap() = visible;
+    refresh(app_handle);
+}
+
+fn overall_status(websites: &[Website]) -> Status {
+    let checked = websites.iter().filter(|w| w.monitor.enabled);
+    let mut status = Status::Idle;
+    for website in checked {
+        let this = match website.state {
+            HealthState::Down => Status::Down,
+            HealthState::Degraded => Status::Degraded,
+            HealthState::Up | HealthState::Maintenance => Status::Up,
+        };
+        status = match (status, this) {
+            (Status::Down, _) | (_, Status::Down) => Status::Down,
+            (Status::Degraded, _) | (_, Status::Degraded) => Status::Degraded,
+            _ => Status::Up,
+        };
+    }
+    status
+}
+
+// "Website Monitor: 3 up, 1 degraded, 0 down" plus the time of the latest check
+fn tooltip(websites: &[Website], pause: Option<GlobalPause>, offline_since: Option<DateTime<Utc>>) -> String {
+    let (mut up, mut degraded, mut down, mut maintenance, mut paused) = (0, 0, 0, 0, 0);
+    for website in websites {
+        if !website.monitor.enabled {
+            paused += 1;
+            continue;
+        }
+        match website.state {
+            HealthState::Up => up += 1,
+            HealthState::Degraded => degraded += 1,
+            HealthState::Down => down += 1,
+            HealthState::Maintenance => maintenance += 1,
+        }
+    }
+    let mut tooltip = format!("Website Monitor: {} up, {} degraded, {} down", up, degraded, down);
+    if maintenance > 0 {
+        tooltip.push_str(&format!(", {} in maintenance", maintenance));
+    }
+    if paused > 0 {
+        tooltip.push_str(&format!(", {} paused", paused));
+    }
+    match pause.map(|p| p.until) {
+        Some(Some(until)) => tooltip.push_str(&format!(
+            "\nMonitoring paused until {}",
+            until.with_timezone(&Local).format("%H:%M")
+        )),
+        Some(None) => tooltip.push_str("\nMonitoring paused"),
+        None => {}
+    }
+    if let Some(since) = offline_since {
+        tooltip.push_str(&format!("\nOffline since {}", since.with_timezone(&Local).format("%H:%M")));
+    }
+    let last_checked = websites
+        .iter()
+        .filter_map(|w| DateTime::parse_from_rfc3339(&w.last_checked).ok())
+        .max();
+    match last_checked {
+        Some(at) => tooltip.push_str(&format!("\nLast check: {}", at.with_timezone(&Local).format("%H:%M:%S"))),
+        None => tooltip.push_str("\nNot checked yet"),
+    }
+    tooltip
+}
+
+fn build_menu(app_handle: &AppHandle, contents: &MenuContents) -> tauri::Result<tauri::menu::Menu<Wry>> {
+    let show = MenuItem::with_id(
+        app_handle,
+        "show",
+        if contents.window_visible { "Hide" } else { "Show" },
+        true,
+        None::<&str>,
+    )?;
+    let monitors: Vec<Submenu<Wry>> = contents
+        .monitors
+        .iter()
+        .map(|entry| monitor_submenu(app_handle, entry, contents.monitoring_paused))
+        .collect::<tauri::Result<_>>()?;
+    let monitor_items: Vec<&dyn IsMenuItem<Wry>> = monitors.iter().map(|m| m as &dyn IsMenuItem<Wry>).collect();
+    let monitors_menu = SubmenuBuilder::new(app_handle, "Monitors")
+        .items(&monitor_items)
+        .build()?;
+    monitors_menu.set_enabled(!monitors.is_empty())?;
+
+    let mut menu = MenuBuilder::new(app_handle)
+        .item(&show)
+        .item(&monitors_menu)
+        .item(&PredefinedMenuItem::separator(app_handle)?);
+    menu = if contents.monitoring_paused {
+        menu.item(&MenuItem::with_id(app_handle, "resume_all", "Resume monitoring", true, None::<&str>)?)
+    } else {
+        menu.item(&MenuItem::with_id(app_handle, "pause_all", "Pause monitoring", true, None::<&str>)?)
+            .item(&MenuItem::with_id(app_handle, "pause_all_1h", "Pause for 1h", true, None::<&str>)?)
+    };
+    menu.item(&PredefinedMenuItem::separator(app_handle)?)
+        .item(&MenuItem::with_id(app_handle, "acknowledge", "Acknowledge", true, None::<&str>)?)
+        .item(&MenuItem::with_id(app_handle, "snooze_1h", "Snooze 1h", true, None::<&str>)?)
+        .item(&PredefinedMenuItem::separator(app_handle)?)
+        .item(&MenuItem::with_id(app_handle, "quit", "Quit", true, None::<&str>)?)
+        .build()
+}
+
+// "Example (down, snoozed)" with check now / open / pause / snooze underneath.
+// Item ids are "<action>:<monitor id>".
+fn monitor_submenu(
+    app_handle: &AppHandle,
+    entry: &MonitorEntry,
+    monitoring_paused: bool,
+) -> tauri::Result<Submenu<Wry>> {
+    let mut label = if entry.enabled {
+        entry.state.label().to_string()
+    } else {
+        "paused".to_string()
+    };
+    if entry.snoozed {
+        label.push_str(", snoozed");
+    }
+    let item = |action: &str, text: &str, enabled: bool| {
+        MenuItem::with_id(app_handle, format!("{}:{}", action, entry.id), text, enabled, None::<&str>)
+    };
+    SubmenuBuilder::new(app_handle, format!("{} ({})", entry.name, label))
+        .item(&item("check", "Check now", entry.enabled && !monitoring_paused)?)
+        .item(&item("open", "Open in browser", true)?)
+        .item(&item("pause", if entry.enabled { "Pause" } else { "Resume" }, true)?)
+        .item(&item(
+            "snooze",
+            if entry.snoozed { "Cancel snooze" } else { "Snooze 1h" },
+            true,
+        )?)
+        .build()
+}
+
+fn handle_menu_event(app: &AppHandle, event: MenuEvent) {
+    let id = event.id.as_ref();
+    match id {
+        "quit" => app.exit(0),
+        "acknowledge" => crate::acknowledge_all_down(app),
+        "snooze_1h" => crate::snooze_all_failing(app, chrono::Duration::hours(1)),
+        "pause_all" => {
+            crate::pause_monitoring_until(app, None);
+        }
+        "pause_all_1h" => {
+            crate::pause_monitoring_until(app, Some(Utc::now() + chrono::Duration::hours(1)));
+        }
+        "resume_all" => crate::resume_all_monitoring(app),
+        "show" => {
+            let visible = *app.state::<AppState>().tray.window_visible.lock().unwrap();
+            set_window_visible(app, !visible);
+        }
+        _ => {
+            if let Some((action, monitor_id)) = id.split_once(':') {
+                if let Err(e) = monitor_action(app, action, monitor_id) {
+                    eprintln!("Tray action {} failed: {}", id, e);
+                }
+            }
+        }
+    }
+}
+
+fn monitor_action(app: &AppHandle, action: &str, monitor_id: &str) -> Result<(), String> {
+    let website = app
+        .state::<AppState>()
+        .websites
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|w| w.monitor.id == monitor_id)
+        .cloned()
+        .ok_or_else(|| format!("no website with id {}", monitor_id))?;
+    match action {
+        // like the window's "check now": runs outside the scheduler
+        "check" => {
+            let app = app.clone();
+            tauri::async_runtime::spawn(async move {
+                let _ = crate::do_check_websites(vec![website], app).await;
+            });
+        }
+        "open" => app
+            .opener()
+            .open_url(&website.monitor.url, None::<&str>)
+            .map_err(|e| e.to_string())?,
+        "pause" => {
+            crate::set_website_enabled(app, monitor_id, !website.monitor.enabled, None)?;
+        }
+        "snooze" => {
+            let snoozed = website.snoozed_until.is_some_and(|until| until > Utc::now());
+            let duration = if snoozed { chrono::Duration::zero() } else { chrono::Duration::hours(1) };
+            crate::snooze_website(app, monitor_id, duration)?;
+        }
+        _ => return Err(format!("unknown action {}", action)),
+    }
+    Ok(())
+}
+
+// The window icon with a status-coloured dot in the bottom-right corner
+fn status_icon(app_handle: &AppHandle, status: Status) -> Image<'static> {
+    let (mut rgba, width, height) = match app_handle.default_window_icon() {
+        Some(icon) => (icon.rgba().to_vec(), icon.width(), icon.height()),
+        None => {
+            let size = FALLBACK_ICON_SIZE;
+            (vec![0; (size * size * 4) as usize], size, size)
+        }
+    };
+    if let Some(rgb) = status.rgb() {
+        let size = width.min(height) as f32;
+        let radius = size * 0.22;
+        let border = (size / 16.0).max(1.0);
+        let (cx, cy) = (width as f32 - radius - border, height as f32 - radius - border);
+        for y in 0..height {
+            for x in 0..width {
+                let distance = ((x as f32 + 0.5 - cx).powi(2) + (y as f32 + 0.5 - cy).powi(2)).sqrt();
+                // a white ring around the dot so it stands out on any icon; edges softened by a pixel
+                let ring = (radius + border - distance + 0.5).clamp(0.0, 1.0);
+                if ring <= 0.0 {
+                    continue;
+                }
+                let dot = (radius - distance + 0.5).clamp(0.0, 1.0);
+                let pixel = &mut rgba[((y * width + x) * 4) as usize..][..4];
+                for channel in 0..3 {
+                    let base = pixel[channel] as f32 * (1.0 - ring) + 255.0 * ring;
+                    pixel[channel] = (rgb[channel] as f32 * dot + base * (1.0 - dot)).round() as u8;
+                }
+                pixel[3] = pixel[3].max((ring * 255.0).round() as u8);
+            }
+        }
+    }
+    Image::new_owned(rgba, width, height)
+}
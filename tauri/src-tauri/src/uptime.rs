@@ -0,0 +1,458 @@
+use chrono::{DateTime, Duration, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::db::Database;
+
+const HOUR_MS: i64 = 3_600_000;
+
+// Upper bounds (ms) of the latency histogram buckets kept per hour; the last bucket is open ended.
+// Percentiles are read off these buckets, so they are approximate by design.
+const LATENCY_BUCKETS_MS: &[u64] = &[
+    25, 50, 75, 100, 150, 200, 300, 400, 500, 750, 1_000, 1_500, 2_000, 3_000, 5_000, 7_500, 10_000,
+    15_000, 30_000,
+];
+
+// Only these states count towards uptime. Anything else (e.g. time excluded from monitoring)
+// closes the previous interval without adding monitored time.
+fn counts_as_down(state: &str) -> Option<bool> {
+    match state {
+        "up" | "degraded" => Some(false),
+        "down" => Some(true),
+        _ => None,
+    }
+}
+
+// Folds one check (or a change such as a pause) into the hourly rollups. The time since the
+// previous record is credited to the state the monitor was in, up to `max_gap_secs` so time the
+// app wasn't running isn't counted. `at` is when the record was applied, not when its check
+// started; a time before the cursor is moved up to it, so no period is counted twice.
+pub fn record_check(
+    db: &Database,
+    monitor_id: &str,
+    at: DateTime<Utc>,
+    state: &str,
+    latency_ms: Option<u64>,
+    max_gap_secs: u64,
+) -> Result<(), String> {
+    db.with_conn(|conn| {
+        let tx = conn.transaction()?;
+        let previous: Option<(i64, String)> = tx
+            .query_row(
+                "SELECT last_checked_at, last_state FROM uptime_cursor WHERE monitor_id = ?1",
+                params![monitor_id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+
+        let now_ms = at
+            .timestamp_millis()
+            .max(previous.as_ref().map_or(i64::MIN, |(last_ms, _)| *last_ms));
+        if let Some((last_ms, last_state)) = previous {
+            if let Some(down) = counts_as_down(&last_state) {
+                let end = now_ms.min(last_ms + max_gap_secs as i64 * 1000);
+                add_interval(&tx, monitor_id, last_ms, end, down)?;
+            }
+        }
+        if let (Some(latency), Some(false)) = (latency_ms, counts_as_down(state)) {
+            add_latency(&tx, monitor_id, hour_start(now_ms), latency)?;
+        }
+        tx.execute(
+            "INSERT INTO uptime_cursor (monitor_id, last_checked_at, last_state) VALUES (?1, ?2, ?3)
+             ON CONFLICT(monitor_id) DO UPDATE SET last_checked_at = ?2, last_state = ?3",
+            params![monitor_id, now_ms, state],
+        )?;
+        tx.commit()
+    })
+}
+
+fn hour_start(ms: i64) -> i64 {
+    ms.div_euclid(HOUR_MS) * HOUR_MS
+}
+
+// Spreads [start, end) across the hour buckets it touches
+fn add_interval(conn: &Connection, monitor_id: &str, start: i64, end: i64, down: bool) -> rusqlite::Result<()> {
+    let mut cursor = start;
+    while cursor < end {
+        let hour = hour_start(cursor);
+        let slice_end = end.min(hour + HOUR_MS);
+        let span = slice_end - cursor;
+        conn.execute(
+            "INSERT INTO uptime_hourly (monitor_id, hour_start, monitored_ms, downtime_ms)
+             VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(monitor_id, hour_start) DO UPDATE SET
+                monitored_ms = monitored_ms + ?3, downtime_ms = downtime_ms + ?4",
+            params![monitor_id, hour, span, if down { span } else { 0 }],
+        )?;
+        cursor = slice_end;
+    }
+    Ok(())
+}
+
+fn add_latency(conn: &Connection, monitor_id: &str, hour: i64, latency_ms: u64) -> rusqlite::Result<()> {
+    let existing: Option<String> = conn
+        .query_row(
+            "SELECT latency_histogram FROM uptime_hourly WHERE monitor_id = ?1 AND hour_start = ?2",
+            params![monitor_id, hour],
+            |row| row.get(0),
+        )
+        .optional()?
+        .flatten();
+    let mut histogram = parse_histogram(existing.as_deref());
+    histogram[bucket_index(latency_ms)] += 1;
+    let histogram = serde_json::to_string(&histogram).unwrap_or_default();
+    conn.execute(
+        "INSERT INTO uptime_hourly (monitor_id, hour_start, latency_sum, latency_count, latency_histogram)
+         VALUES (?1, ?2, ?3, 1, ?4)
+         ON CONFLICT(monitor_id, hour_start) DO UPDATE SET
+            latency_sum = latency_sum + ?3, latency_count = latency_count + 1, latency_histogram = ?4",
+        params![monitor_id, hour, latency_ms as i64, histogram],
+    )?;
+    Ok(())
+}
+
+fn bucket_index(latency_ms: u64) -> usize {
+    LATENCY_BUCKETS_MS
+        .iter()
+        .position(|bound| latency_ms <= *bound)
+        .unwrap_or(LATENCY_BUCKETS_MS.len())
+}
+
+fn parse_histogram(json: Option<&str>) -> Vec<u64> {
+    let mut histogram: Vec<u64> = json.and_then(|j| serde_json::from_str(j).ok()).unwrap_or_default();
+    histogram.resize(LATENCY_BUCKETS_MS.len() + 1, 0);
+    histogram
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Window {
+    Hours24,
+    Days7,
+    Days30,
+    Days90,
+    Custom(DateTime<Utc>, DateTime<Utc>),
+}
+
+impl Window {
+    pub fn parse(name: &str, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) -> Result<Window, String> {
+        match name {
+            "24h" => Ok(Window::Hours24),
+            "7d" => Ok(Window::Days7),
+            "30d" => Ok(Window::Days30),
+            "90d" => Ok(Window::Days90),
+            "custom" => match (from, to) {
+                (Some(from), Some(to)) if from < to => Ok(Window::Custom(from, to)),
+                (Some(_), Some(_)) => Err("custom range must end after it starts".to_string()),
+                _ => Err("custom range needs both from and to".to_string()),
+            },
+            other => Err(format!("unknown window '{}' (use 24h, 7d, 30d, 90d or custom)", other)),
+        }
+    }
+
+    pub fn range(&self, now: DateTime<Utc>) -> (DateTime<Utc>, DateTime<Utc>) {
+        match *self {
+            Window::Hours24 => (now - Duration::hours(24), now),
+            Window::Days7 => (now - Duration::days(7), now),
+            Window::Days30 => (now - Duration::days(30), now),
+            Window::Days90 => (now - Duration::days(90), now),
+            Window::Custom(from, to) => (from, to),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct UptimeStats {
+    // None when nothing was monitored in the window
+    pub uptime_percent: Option<f64>,
+    pub monitored_secs: u64,
+    pub downtime_secs: u64,
+    pub mean_latency_ms: Option<f64>,
+    pub p95_latency_ms: Option<u64>,
+    pub p99_latency_ms: Option<u64>,
+    pub sla_target: Option<f64>,
+    // how much of the allowed downtime is left, in percent of the allowance and in seconds
+    pub error_budget_remaining_percent: Option<f64>,
+    pub error_budget_remaining_secs: Option<i64>,
+}
+
+#[derive(Default, Clone)]
+struct Totals {
+    monitored_ms: i64,
+    downtime_ms: i64,
+    latency_sum: i64,
+    latency_count: i64,
+    histogram: Vec<u64>,
+}
+
+impl Totals {
+    fn merge(&mut self, other: &Totals) {
+        self.monitored_ms += other.monitored_ms;
+        self.downtime_ms += other.downtime_ms;
+        self.latency_sum += other.latency_sum;
+        self.latency_count += other.latency_count;
+        self.histogram.resize(LATENCY_BUCKETS_MS.len() + 1, 0);
+        for (mine, theirs) in self.histogram.iter_mut().zip(&other.histogram) {
+            *mine += theirs;
+        }
+    }
+
+    fn stats(&self, sla_target: Option<f64>) -> UptimeStats {
+        let uptime_percent = (self.monitored_ms > 0)
+            .then(|| (self.monitored_ms - self.downtime_ms) as f64 / self.monitored_ms as f64 * 100.0);
+        let (budget_percent, budget_secs) = match (sla_target, self.monitored_ms > 0) {
+            (Some(target), true) => {
+                let allowed_ms = (1.0 - target / 100.0) * self.monitored_ms as f64;
+                let remaining_ms = allowed_ms - self.downtime_ms as f64;
+                let percent = if allowed_ms > 0.0 { remaining_ms / allowed_ms * 100.0 } else { 0.0 };
+                (Some(percent), Some((remaining_ms / 1000.0).round() as i64))
+            }
+            _ => (None, None),
+        };
+        UptimeStats {
+            uptime_percent,
+            monitored_secs: (self.monitored_ms / 1000) as u64,
+            downtime_secs: (self.downtime_ms / 1000) as u64,
+            mean_latency_ms: (self.latency_count > 0).then(|| self.latency_sum as f64 / self.latency_count as f64),
+            p95_latency_ms: percentile(&self.histogram, 0.95),
+            p99_latency_ms: percentile(&self.histogram, 0.99),
+            sla_target,
+            error_budget_remaining_percent: budget_percent,
+            error_budget_remaining_secs: budget_secs,
+        }
+    }
+}
+
+// Upper bound of the bucket holding the given rank; the open-ended bucket reports its lower bound
+fn percentile(histogram: &[u64], p: f64) -> Option<u64> {
+    let total: u64 = histogram.iter().sum();
+    if total == 0 {
+        return None;
+    }
+    let rank = (p * total as f64).ceil() as u64;
+    let mut seen = 0;
+    for (i, count) in histogram.iter().enumerate() {
+        seen += count;
+        if seen >= rank {
+            return Some(*LATENCY_BUCKETS_MS.get(i).unwrap_or(LATENCY_BUCKETS_MS.last().unwrap()));
+        }
+    }
+    LATENCY_BUCKETS_MS.last().copied()
+}
+
+// Sums the hourly rollups overlapping [from, to) for each monitor. Edges are rounded out to
+// whole hours, which is what keeps this cheap.
+fn totals_by_monitor(
+    db: &Database,
+    monitor_ids: &[String],
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Result<HashMap<String, Totals>, String> {
+    let from_hour = hour_start(from.timestamp_millis());
+    let to_ms = to.timestamp_millis();
+    db.with_conn(|conn| {
+        let mut stmt = conn.prepare(
+            "SELECT monitor_id, monitored_ms, downtime_ms, latency_sum, latency_count, latency_histogram
+             FROM uptime_hourly WHERE hour_start >= ?1 AND hour_start < ?2",
+        )?;
+        let mut totals: HashMap<String, Totals> = HashMap::new();
+        let rows = stmt.query_map(params![from_hour, to_ms], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                Totals {
+                    monitored_ms: row.get(1)?,
+                    downtime_ms: row.get(2)?,
+                    latency_sum: row.get(3)?,
+                    latency_count: row.get(4)?,
+                    histogram: parse_histogram(row.get::<_, Option<String>>(5)?.as_deref()),
+                },
+            ))
+        })?;
+        for row in rows {
+            let (monitor_id, hour) = row?;
+            if monitor_ids.contains(&monitor_id) {
+                totals.entry(monitor_id).or_default().merge(&hour);
+            }
+        }
+        Ok(totals)
+    })
+}
+
+pub struct MonitorRef {
+    pub id: String,
+    pub name: String,
+    pub group: Option<String>,
+    pub sla_target: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MonitorUptime {
+    pub monitor_id: String,
+    pub name: String,
+    pub group: Option<String>,
+    #[serde(flatten)]
+    pub stats: UptimeStats,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GroupUptime {
+    pub group: String,
+    pub monitor_ids: Vec<String>,
+    #[serde(flatten)]
+    pub stats: UptimeStats,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UptimeReport {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub monitors: Vec<MonitorUptime>,
+    pub groups: Vec<GroupUptime>,
+}
+
+pub fn report(db: &Database, monitors: &[MonitorRef], window: Window) -> Result<UptimeReport, String> {
+    let (from, to) = window.range(Utc::now());
+    let ids: Vec<String> = monitors.iter().map(|m| m.id.clone()).collect();
+    let totals = totals_by_monitor(db, &ids, from, to)?;
+
+    let mut groups: Vec<(String, Vec<&MonitorRef>)> = Vec::new();
+    for monitor in monitors {
+        if let Some(group) = &monitor.group {
+            match groups.iter_mut().find(|(name, _)| name == group) {
+                Some((_, members)) => members.push(monitor),
+                None => groups.push((group.clone(), vec![monitor])),
+            }
+        }
+    }
+
+    Ok(UptimeReport {
+        from,
+        to,
+        monitors: monitors
+            .iter()
+            .map(|m| MonitorUptime {
+                monitor_id: m.id.clone(),
+                name: m.name.clone(),
+                group: m.group.clone(),
+                stats: totals.get(&m.id).cloned().unwrap_or_default().stats(m.sla_target),
+            })
+            .collect(),
+        groups: groups
+            .into_iter()
+            .map(|(group, members)| {
+                let mut combined = Totals::default();
+                for member in &members {
+                    if let Some(t) = totals.get(&member.id) {
+                        combined.merge(t);
+                    }
+                }
+                // a group is held to its strictest member's target
+                let target = members
+                    .iter()
+                    .filter_map(|m| m.sla_target)
+                    .fold(None, |acc: Option<f64>, t| Some(acc.map_or(t, |a| a.max(t))));
+                GroupUptime {
+                    group,
+                    monitor_ids: members.iter().map(|m| m.id.clone()).collect(),
+                    stats: combined.stats(target),
+                }
+            })
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use std::path::Path;
+
+    fn start() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, 2, 12, 0, 0).unwrap()
+    }
+
+    fn totals(db: &Database) -> Totals {
+        let ids = vec!["m".to_string()];
+        let (from, to) = (start() - Duration::days(1), start() + Duration::days(1));
+        totals_by_monitor(db, &ids, from, to).unwrap().remove("m").unwrap_or_default()
+    }
+
+    #[test]
+    fn only_up_degraded_and_down_count() {
+        assert_eq!(counts_as_down("up"), Some(false));
+        assert_eq!(counts_as_down("degraded"), Some(false));
+        assert_eq!(counts_as_down("down"), Some(true));
+        assert_eq!(counts_as_down("paused"), None);
+        assert_eq!(counts_as_down("maintenance"), None);
+    }
+
+    #[test]
+    fn time_is_credited_to_the_previous_state() {
+        let db = Database::open(Path::new(":memory:")).unwrap();
+        record_check(&db, "m", start(), "up", Some(100), 600).unwrap();
+        record_check(&db, "m", start() + Duration::seconds(60), "down", None, 600).unwrap();
+        record_check(&db, "m", start() + Duration::seconds(90), "up", Some(100), 600).unwrap();
+        let totals = totals(&db);
+        assert_eq!(totals.monitored_ms, 90_000);
+        assert_eq!(totals.downtime_ms, 30_000);
+        assert_eq!(totals.latency_count, 2);
+    }
+
+    #[test]
+    fn gaps_are_capped_at_max_gap() {
+        let db = Database::open(Path::new(":memory:")).unwrap();
+        record_check(&db, "m", start(), "down", None, 120).unwrap();
+        record_check(&db, "m", start() + Duration::hours(2), "up", None, 120).unwrap();
+        let totals = totals(&db);
+        assert_eq!(totals.monitored_ms, 120_000);
+        assert_eq!(totals.downtime_ms, 120_000);
+    }
+
+    #[test]
+    fn excluded_states_add_no_time() {
+        let db = Database::open(Path::new(":memory:")).unwrap();
+        record_check(&db, "m", start(), "up", None, 600).unwrap();
+        record_check(&db, "m", start() + Duration::seconds(60), "paused", None, 600).unwrap();
+        record_check(&db, "m", start() + Duration::seconds(300), "up", None, 600).unwrap();
+        assert_eq!(totals(&db).monitored_ms, 60_000);
+    }
+
+    #[test]
+    fn a_record_before_the_cursor_is_moved_up_to_it() {
+        let db = Database::open(Path::new(":memory:")).unwrap();
+        record_check(&db, "m", start(), "up", None, 600).unwrap();
+        record_check(&db, "m", start() + Duration::seconds(60), "down", None, 600).unwrap();
+        // applied late with an older time; nothing is counted twice or backwards
+        record_check(&db, "m", start() + Duration::seconds(30), "up", None, 600).unwrap();
+        record_check(&db, "m", start() + Duration::seconds(120), "up", None, 600).unwrap();
+        let totals = totals(&db);
+        assert_eq!(totals.monitored_ms, 120_000);
+        assert_eq!(totals.downtime_ms, 0);
+    }
+
+    #[test]
+    fn intervals_are_split_across_hours() {
+        let db = Database::open(Path::new(":memory:")).unwrap();
+        let before = start() - Duration::seconds(30);
+        record_check(&db, "m", before, "up", None, 600).unwrap();
+        record_check(&db, "m", start() + Duration::seconds(30), "up", None, 600).unwrap();
+        let hours: i64 = db
+            .with_conn(|conn| conn.query_row("SELECT COUNT(*) FROM uptime_hourly", [], |row| row.get(0)))
+            .unwrap();
+        assert_eq!(hours, 2);
+        assert_eq!(totals(&db).monitored_ms, 60_000);
+    }
+
+    #[test]
+    fn percentiles_report_bucket_upper_bounds() {
+        let mut histogram = vec![0; LATENCY_BUCKETS_MS.len() + 1];
+        assert_eq!(percentile(&histogram, 0.95), None);
+        histogram[bucket_index(20)] = 94;
+        histogram[bucket_index(450)] = 5;
+        histogram[bucket_index(60_000)] = 1;
+        assert_eq!(percentile(&histogram, 0.5), Some(25));
+        assert_eq!(percentile(&histogram, 0.95), Some(500));
+        assert_eq!(percentile(&histogram, 0.99), Some(500));
+        assert_eq!(percentile(&histogram, 1.0), Some(30_000));
+    }
+}
@@ -135,7 +135,7 @@ async function checkWebsites() {
 
 listen('website_check_complete', (event: any) => {
     if (checkElem) {
-        checkElem.textContent = event.payload;
+        checkElem.textContent = event.payload.message;
     }
 });
 